@@ -1,7 +1,8 @@
 use criterion::{Criterion, black_box, criterion_group, criterion_main};
-use std::rc::Rc;
 use std::sync::Arc;
-use weakref::{Own, pin, refer};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use weakref::{Domain, Own, pin, refer};
 
 fn benchmark_own_box_creation(c: &mut Criterion) {
     c.bench_function("own_new_box", |b| {
@@ -186,6 +187,85 @@ fn benchmark_heavy_workload_arc(c: &mut Criterion) {
     });
 }
 
+const CONTENDED_THREADS: usize = 4;
+
+/// Every thread repeatedly revokes its own owner (the same compare-exchange as
+/// dropping) and reads it back. The owners are created together, so without
+/// padding their counters share a cache line.
+fn contended_kill_get(domain: &Domain, iters: u64) -> Duration {
+    let owners: Vec<_> = (0..CONTENDED_THREADS)
+        .map(|i| domain.own(Box::new(i)))
+        .collect();
+    let start = Instant::now();
+    std::thread::scope(|s| {
+        for mut owner in owners {
+            s.spawn(move || {
+                for _ in 0..iters {
+                    let weak = owner.revoke();
+                    black_box(weak.get(&pin()));
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+/// One thread repeatedly revokes an owner, while the others read unrelated
+/// owners whose counters were allocated next to it.
+fn contended_get(domain: &Domain, iters: u64) -> Duration {
+    let mut writer = domain.own(Box::new(0));
+    let readers: Vec<_> = (1..CONTENDED_THREADS)
+        .map(|i| domain.own(Box::new(i)))
+        .collect();
+    let done = AtomicBool::new(false);
+    let start = Instant::now();
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            while !done.load(Ordering::Relaxed) {
+                black_box(writer.revoke());
+            }
+        });
+        let handles: Vec<_> = readers
+            .iter()
+            .map(|owner| {
+                let weak = owner.refer();
+                s.spawn(move || {
+                    for _ in 0..iters {
+                        black_box(weak.get(&pin()));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        done.store(true, Ordering::Relaxed);
+    });
+    start.elapsed()
+}
+
+fn benchmark_contended_kill_get(c: &mut Criterion) {
+    let mut group = c.benchmark_group("contended_kill_get");
+    group.bench_function("packed", |b| {
+        b.iter_custom(|iters| contended_kill_get(&Domain::new(), iters))
+    });
+    group.bench_function("padded", |b| {
+        b.iter_custom(|iters| contended_kill_get(&Domain::padded(), iters))
+    });
+    group.finish();
+}
+
+fn benchmark_contended_get(c: &mut Criterion) {
+    let mut group = c.benchmark_group("contended_get");
+    group.bench_function("packed", |b| {
+        b.iter_custom(|iters| contended_get(&Domain::new(), iters))
+    });
+    group.bench_function("padded", |b| {
+        b.iter_custom(|iters| contended_get(&Domain::padded(), iters))
+    });
+    group.finish();
+}
+
 criterion_group!(
     benches,
     benchmark_own_box_creation,
@@ -202,6 +282,8 @@ criterion_group!(
     benchmark_comparison_arc_weak_clone,
     benchmark_heavy_workload,
     benchmark_heavy_workload_arc,
+    benchmark_contended_kill_get,
+    benchmark_contended_get,
 );
 
 criterion_main!(benches);
@@ -0,0 +1,70 @@
+/*
+ * C ABI for the weakref crate, enabled by its `ffi` feature.
+ *
+ * An owner holds an opaque payload: a data pointer and a destructor. Dropping
+ * the owner kills every reference, and the destructor runs once no thread is
+ * still pinned. The payload may be read from, and destroyed on, any thread.
+ *
+ * References are plain values which may be copied freely and kept forever.
+ * To read a payload, pin the thread, get the data pointer, and unpin once
+ * finished with it. A guard must be unpinned on the thread which pinned it.
+ */
+
+#ifndef WEAKREF_H
+#define WEAKREF_H
+
+#include <stdbool.h>
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct weakref_own weakref_own_t;
+typedef struct weakref_guard weakref_guard_t;
+
+typedef struct weakref_ref {
+    const void *current_gen;
+    size_t expected_gen;
+    const void *pointer;
+} weakref_ref_t;
+
+/* Creates an owner for the payload. Never returns NULL. */
+weakref_own_t *weakref_own_new(void *data, void (*destroy)(void *data));
+
+/* Provides a reference to the owner's payload. */
+weakref_ref_t weakref_own_refer(const weakref_own_t *own);
+
+/* Drops the owner, killing every reference. Does nothing if own is NULL. */
+void weakref_own_drop(weakref_own_t *own);
+
+/* Returns a reference which is never alive. */
+weakref_ref_t weakref_ref_null(void);
+
+/* Returns true if the owner has not been dropped. */
+bool weakref_ref_is_alive(weakref_ref_t weak);
+
+/* Pins the current thread. Never returns NULL. */
+weakref_guard_t *weakref_pin(void);
+
+/* Unpins the current thread. Does nothing if guard is NULL. */
+void weakref_unpin(weakref_guard_t *guard);
+
+/*
+ * Tries to run any destructors which have become safe, including those
+ * deferred by the current thread. Otherwise they may wait until the thread
+ * has deferred enough of them.
+ */
+void weakref_flush(void);
+
+/*
+ * Provides the payload's data pointer if the owner is alive, or NULL if it was
+ * dropped. The payload is not destroyed until the guard is unpinned.
+ */
+void *weakref_ref_get(weakref_ref_t weak, const weakref_guard_t *guard);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,257 @@
+use crate::guts::{
+    GenerationCounter, Ref, advance_generation, can_reuse, new_generation_counter,
+    recycle_generation_counter,
+};
+use crate::stats;
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+use crossbeam_epoch::pin;
+use crossbeam_queue::SegQueue;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[cfg(not(loom))]
+use core::sync::atomic::Ordering;
+#[cfg(loom)]
+use loom::sync::atomic::Ordering;
+
+const CHUNK_SIZE: usize = 64;
+
+struct Slot<T> {
+    value: UnsafeCell<MaybeUninit<T>>,
+    current_gen: GenerationCounter,
+}
+
+// SAFETY: The value is only written by the arena while the slot is vacant, and
+// only dropped by a deferred function once no reader can observe it. Sharing
+// the value itself goes through [Ref], which requires `T: Sync`.
+unsafe impl<T: Send> Send for Slot<T> {}
+unsafe impl<T: Send> Sync for Slot<T> {}
+
+impl<T> Slot<T> {
+    fn as_ptr(&self) -> NonNull<T> {
+        NonNull::new(self.value.get().cast::<T>()).unwrap()
+    }
+}
+
+/// Stores many values contiguously in fixed slots, handing out a [Ref] for each.
+///
+/// Every slot has its own generation counter, so removing a value kills all of
+/// its references exactly like dropping an [Own](crate::Own). But inserting
+/// does not need a separate heap allocation per value, and the slot memory is
+/// reused once the epoch allows it.
+///
+/// ```
+///# use weakref::{Arena, pin};
+/// let mut arena = Arena::new();
+/// let a = arena.insert(1);
+/// let b = arena.insert(2);
+/// assert_eq!(a.get(&pin()), Some(&1));
+///
+/// assert!(arena.remove(a));
+/// assert_eq!(a.get(&pin()), None);
+/// assert_eq!(b.get(&pin()), Some(&2));
+/// ```
+pub struct Arena<T: Send + 'static> {
+    /// Chunks are shared with deferred destructors, which may outlive the arena.
+    chunks: Vec<Arc<[Slot<T>]>>,
+    /// The expected generation of every occupied slot.
+    occupied: Vec<Option<usize>>,
+    /// Finds the slot index for the address of a generation counter.
+    indices: HashMap<usize, usize>,
+    /// Vacant slots, including those whose value was dropped after removal.
+    vacant: Arc<SegQueue<usize>>,
+    len: usize,
+}
+
+impl<T: Send + 'static> Arena<T> {
+    /// Creates an empty arena. No slots are allocated until the first insert.
+    pub fn new() -> Self {
+        Arena {
+            chunks: Vec::new(),
+            occupied: Vec::new(),
+            indices: HashMap::new(),
+            vacant: Arc::new(SegQueue::new()),
+            len: 0,
+        }
+    }
+
+    /// The number of values currently in the arena.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the arena holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Moves the value into a vacant slot and provides the weak pointer.
+    pub fn insert(&mut self, value: T) -> Ref<T> {
+        self.insert_indexed(value).1
+    }
+
+    /// Like [Arena::insert], but also provides the slot index.
+    pub(crate) fn insert_indexed(&mut self, value: T) -> (usize, Ref<T>) {
+        let index = match self.vacant.pop() {
+            Some(index) => index,
+            None => self.grow(),
+        };
+        let slot = self.slot(index);
+        // SAFETY: The slot is vacant, so no other thread can access the value
+        unsafe { (*slot.value.get()).write(value) };
+        let expected_gen = slot.current_gen.load(Ordering::Acquire);
+        let weak = Ref {
+            current_gen: slot.current_gen,
+            expected_gen,
+            pointer: Some(slot.as_ptr()),
+            _guard: PhantomData,
+        };
+        self.occupied[index] = Some(expected_gen);
+        self.len += 1;
+        (index, weak)
+    }
+
+    /// Provides the weak pointer for the slot, if it is occupied at `expected_gen`.
+    pub(crate) fn refer_indexed(&self, index: usize, expected_gen: usize) -> Option<Ref<T>> {
+        if *self.occupied.get(index)? != Some(expected_gen) {
+            return None;
+        }
+        let slot = self.slot(index);
+        Some(Ref {
+            current_gen: slot.current_gen,
+            expected_gen,
+            pointer: Some(slot.as_ptr()),
+            _guard: PhantomData,
+        })
+    }
+
+    /// Removes the value which `weak` refers to (or was mapped from), killing
+    /// all of its references. The value is dropped once no thread can still be
+    /// reading it.
+    ///
+    /// Returns false if `weak` is dead or was not created by this arena.
+    pub fn remove<R: ?Sized>(&mut self, weak: Ref<R>) -> bool {
+        let Some(index) = self.index_of(weak) else {
+            return false;
+        };
+        self.occupied[index] = None;
+        self.len -= 1;
+
+        let chunk = self.chunks[index / CHUNK_SIZE].clone();
+        let current_gen = chunk[index % CHUNK_SIZE].current_gen;
+        if !advance_generation(current_gen, weak.expected_gen) {
+            panic!("Tried to remove a dead arena slot");
+        }
+
+        // The slot can only be reused once the value is gone, so it is sent
+        // back along with the destructor.
+        let vacant = self.vacant.clone();
+        let reusable = can_reuse(weak.expected_gen + 1);
+        if !reusable {
+            // The slot is never handed out again, so this is the only time it is counted.
+            stats::counter_retired();
+        }
+        pin().defer(move || {
+            let slot = &chunk[index % CHUNK_SIZE];
+            // SAFETY: The slot was occupied, and no reader can access it anymore
+            unsafe { slot.as_ptr().drop_in_place() };
+            if reusable {
+                vacant.push(index);
+            }
+        });
+        true
+    }
+
+    pub(crate) fn index_of<R: ?Sized>(&self, weak: Ref<R>) -> Option<usize> {
+        let key = weak.current_gen as *const _ as usize;
+        let index = *self.indices.get(&key)?;
+        if self.occupied[index] == Some(weak.expected_gen) {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    /// The largest index the next call to [Arena::insert] can use.
+    pub(crate) fn next_index_bound(&self) -> usize {
+        let capacity = self.chunks.len() * CHUNK_SIZE;
+        // Other threads only ever add vacant slots, so one will still be there.
+        if self.vacant.is_empty() {
+            capacity
+        } else {
+            capacity - 1
+        }
+    }
+
+    fn slot(&self, index: usize) -> &Slot<T> {
+        &self.chunks[index / CHUNK_SIZE][index % CHUNK_SIZE]
+    }
+
+    /// Allocates a new chunk of slots, returning one and marking the rest vacant.
+    fn grow(&mut self) -> usize {
+        let base = self.chunks.len() * CHUNK_SIZE;
+        let chunk: Arc<[Slot<T>]> = (0..CHUNK_SIZE)
+            .map(|_| Slot {
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+                current_gen: new_generation_counter(),
+            })
+            .collect();
+        for (offset, slot) in chunk.iter().enumerate() {
+            let key = slot.current_gen as *const _ as usize;
+            self.indices.insert(key, base + offset);
+        }
+        self.chunks.push(chunk);
+        self.occupied.resize(base + CHUNK_SIZE, None);
+        for index in base + 1..base + CHUNK_SIZE {
+            self.vacant.push(index);
+        }
+        base
+    }
+}
+
+impl<T: Send + 'static> Default for Arena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Send + 'static> Drop for Arena<T> {
+    fn drop(&mut self) {
+        let guard = pin();
+        let mut occupied = Vec::with_capacity(self.len);
+        for (index, expected_gen) in self.occupied.iter().enumerate() {
+            if let Some(expected_gen) = *expected_gen {
+                if !advance_generation(self.slot(index).current_gen, expected_gen) {
+                    panic!("Tried to remove a dead arena slot");
+                }
+                if !can_reuse(expected_gen + 1) {
+                    stats::counter_retired();
+                }
+                occupied.push(index);
+            }
+        }
+
+        // Every counter is now past any live reference, so they can be reused
+        // immediately even though the slots themselves cannot. The rest were
+        // already counted as retired when they were killed.
+        for chunk in &self.chunks {
+            for slot in chunk.iter() {
+                if can_reuse(slot.current_gen.load(Ordering::Acquire)) {
+                    recycle_generation_counter(slot.current_gen);
+                }
+            }
+        }
+
+        let chunks = std::mem::take(&mut self.chunks);
+        guard.defer(move || {
+            for index in occupied {
+                let slot = &chunks[index / CHUNK_SIZE][index % CHUNK_SIZE];
+                // SAFETY: The slot was occupied, and no reader can access it anymore
+                unsafe { slot.as_ptr().drop_in_place() };
+            }
+        });
+    }
+}
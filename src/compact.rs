@@ -0,0 +1,213 @@
+use crate::guts::{GenerationCounter, Ref};
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use crossbeam_epoch::{Guard, pin};
+use std::fmt;
+use std::sync::RwLock;
+
+/// Counters per block in the directory. Larger blocks are split up.
+const DIRECTORY_BLOCK: usize = 256;
+const PAGE_SIZE: usize = 4096;
+/// The last block id is never registered, so that [CompactRef::null] can use it.
+const MAX_BLOCKS: usize = (u32::MAX as usize) / DIRECTORY_BLOCK;
+
+/// Finds the counter for an index, where `index / DIRECTORY_BLOCK` is the
+/// block id and the rest is the offset within the block.
+static PAGES: [AtomicPtr<Page>; MAX_BLOCKS.div_ceil(PAGE_SIZE)] =
+    [const { AtomicPtr::new(core::ptr::null_mut()) }; MAX_BLOCKS.div_ceil(PAGE_SIZE)];
+
+/// The address of the first counter in each block (or 0 if not registered),
+/// and the distance between counters as a shift.
+type Page = [(AtomicUsize, AtomicUsize); PAGE_SIZE];
+
+/// Finds the index for a counter, sorted by address.
+static RANGES: RwLock<Vec<Range>> = RwLock::new(Vec::new());
+
+struct Range {
+    start: usize,
+    end: usize,
+    stride: usize,
+    block: u32,
+}
+
+/// Gives ids to a newly leaked block of `len` counters, `stride` bytes apart.
+pub(crate) fn register_block(first: GenerationCounter, len: usize, stride: usize) {
+    let first = first as *const _ as usize;
+    let mut ranges = RANGES.write().unwrap();
+    for chunk in (0..len).step_by(DIRECTORY_BLOCK) {
+        let block = ranges.len();
+        if block >= MAX_BLOCKS {
+            // Counters past this point can't be used by CompactRef.
+            return;
+        }
+        let start = first + chunk * stride;
+        let end = first + len.min(chunk + DIRECTORY_BLOCK) * stride;
+        let page = &PAGES[block / PAGE_SIZE];
+        let mut entries = page.load(Ordering::Acquire);
+        if entries.is_null() {
+            let new: Box<Page> =
+                Box::new([const { (AtomicUsize::new(0), AtomicUsize::new(0)) }; PAGE_SIZE]);
+            entries = Box::into_raw(new);
+            // Only ever written with RANGES locked
+            page.store(entries, Ordering::Release);
+        }
+        // SAFETY: Pages are never freed
+        let entries = unsafe { &*entries };
+        let (entry_start, entry_shift) = &entries[block % PAGE_SIZE];
+        entry_shift.store(stride.trailing_zeros() as usize, Ordering::Relaxed);
+        entry_start.store(start, Ordering::Release);
+        let index = ranges.partition_point(|range| range.start < start);
+        let block = block as u32;
+        ranges.insert(
+            index,
+            Range {
+                start,
+                end,
+                stride,
+                block,
+            },
+        );
+    }
+}
+
+fn index_of(counter: GenerationCounter) -> Option<u32> {
+    let addr = counter as *const _ as usize;
+    let ranges = RANGES.read().unwrap();
+    let range = &ranges[ranges
+        .partition_point(|range| range.start <= addr)
+        .checked_sub(1)?];
+    if addr >= range.end {
+        return None;
+    }
+    let offset = (addr - range.start) / range.stride;
+    Some(range.block * DIRECTORY_BLOCK as u32 + offset as u32)
+}
+
+fn counter_at(index: u32) -> Option<GenerationCounter> {
+    let block = index as usize / DIRECTORY_BLOCK;
+    let page = PAGES[block / PAGE_SIZE].load(Ordering::Acquire);
+    if page.is_null() {
+        return None;
+    }
+    // SAFETY: Pages are never freed
+    let (start, shift) = &unsafe { &*page }[block % PAGE_SIZE];
+    let start = start.load(Ordering::Acquire);
+    if start == 0 {
+        return None;
+    }
+    let shift = shift.load(Ordering::Relaxed);
+    let addr = start + ((index as usize % DIRECTORY_BLOCK) << shift);
+    // SAFETY: Registered blocks are leaked, so every counter in them lives forever
+    Some(unsafe { &*(addr as *const _) })
+}
+
+/// A 16 byte weak reference, for storing in bulk.
+///
+/// [Ref] takes 24 bytes because it points directly at its generation counter,
+/// and stores a full `usize` generation. [CompactRef] instead stores a 32-bit
+/// index into a global directory of counters, and a 32-bit generation.
+/// Counters are retired once they reach `u32::MAX` so generations always fit.
+///
+/// Converting from a live [Ref] and back is lossless. Converting from a [Ref]
+/// costs a search of the directory, but [CompactRef::get] only adds two
+/// uncontended loads to [Ref::get].
+/// ```
+///# use weakref::{CompactRef, Own, Ref, pin};
+/// let data = Own::new_box(42);
+/// let compact = CompactRef::from(data.refer());
+/// assert_eq!(size_of_val(&compact), 16);
+/// assert_eq!(compact.get(&pin()), Some(&42));
+/// assert_eq!(Ref::from(compact).id(), data.id());
+///
+/// drop(data);
+/// assert_eq!(compact.get(&pin()), None);
+/// ```
+#[repr(C)]
+pub struct CompactRef<T> {
+    index: u32,
+    generation: u32,
+    pointer: Option<NonNull<T>>,
+    _guard: PhantomData<Ref<T>>,
+}
+
+unsafe impl<T: Sync> Send for CompactRef<T> {}
+unsafe impl<T: Sync> Sync for CompactRef<T> {}
+
+impl<T> Clone for CompactRef<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for CompactRef<T> {}
+
+impl<T> CompactRef<T> {
+    /// Returns a fake reference where [CompactRef::get] is always None, as if the owner was dropped.
+    pub const fn null() -> Self {
+        CompactRef {
+            index: u32::MAX,
+            generation: 0,
+            pointer: None,
+            _guard: PhantomData,
+        }
+    }
+
+    /// Check if the original owner has been dropped. If it is alive, return the reference.
+    ///
+    /// See [Ref::get].
+    pub fn get(self, guard: &Guard) -> Option<&T> {
+        Ref::from(self).get(guard)
+    }
+
+    /// [Pin](pin) the current thread and check if the owner has been dropped. If it is alive, call `func` and return the output.
+    pub fn inspect<O>(self, func: impl FnOnce(&T) -> O) -> Option<O> {
+        self.get(&pin()).map(func)
+    }
+
+    /// Checks whether the owner has been dropped, as with [Ref::is_alive].
+    pub fn is_alive(&self) -> bool {
+        Ref::from(*self).is_alive()
+    }
+}
+
+impl<T> From<Ref<T>> for CompactRef<T> {
+    /// Dead references may become [CompactRef::null].
+    fn from(weak: Ref<T>) -> Self {
+        let Some(index) = index_of(weak.current_gen) else {
+            return CompactRef::null();
+        };
+        let Ok(generation) = u32::try_from(weak.expected_gen) else {
+            return CompactRef::null();
+        };
+        CompactRef {
+            index,
+            generation,
+            pointer: weak.pointer,
+            _guard: PhantomData,
+        }
+    }
+}
+
+impl<T> From<CompactRef<T>> for Ref<T> {
+    fn from(compact: CompactRef<T>) -> Self {
+        match counter_at(compact.index) {
+            Some(current_gen) => Ref {
+                current_gen,
+                expected_gen: compact.generation as usize,
+                pointer: compact.pointer,
+                _guard: PhantomData,
+            },
+            None => Ref::null(),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for CompactRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.get(&pin()) {
+            Some(live) => write!(f, "CompactRef::Live({live:?})"),
+            None => f.debug_tuple("CompactRef::Dead").finish_non_exhaustive(),
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use crate::guts::{
+    GenerationCounter, IsPtr, Own, RefId, counter_allocation_forbidden, leak_padded_block,
+    new_generation_counter, recycle_generation_counter,
+};
+use crate::reclaim::{Epoch, Reclaim};
+use crossbeam_epoch::Guard;
+use crossbeam_queue::SegQueue;
+use std::fmt;
+use std::sync::Arc;
+
+/// A group of owners with its own pool of generation counters.
+///
+/// Killed counters of owners created with [Domain::own] go back to the
+/// domain's pool instead of the global one, which keeps [padded](Domain::padded)
+/// counters apart from the packed ones, and lets a subsystem's counter usage
+/// be measured with [Domain::pooled_counters]. Once the domain and all of its
+/// owners are dropped, its counters are returned to the global pool.
+///
+/// Only the counter pool is isolated. Destructors are deferred to the global
+/// epoch collector like those of any other [Own], so a thread pinned
+/// anywhere still holds them up. References are ordinary [Ref](crate::Ref)s
+/// and are read with the global [pin](crate::pin).
+///
+/// ```
+///# use weakref::{Domain, pin};
+/// let domain = Domain::new();
+/// let data = domain.own(Box::new(42));
+/// let weak = data.refer();
+/// assert_eq!(weak.get(&pin()), Some(&42));
+///
+/// drop(data);
+/// drop(domain);
+/// assert_eq!(weak.get(&pin()), None);
+/// ```
+#[derive(Clone)]
+pub struct Domain {
+    inner: Arc<DomainInner>,
+}
+
+struct DomainInner {
+    recycler: SegQueue<GenerationCounter>,
+    padded: bool,
+}
+
+impl Domain {
+    /// Creates an empty domain.
+    pub fn new() -> Self {
+        Self::with_padding(false)
+    }
+
+    /// Creates an empty domain where every generation counter gets a cache
+    /// line to itself.
+    ///
+    /// Normally counters are packed tightly, so owners which are killed or
+    /// revoked on one core can slow down access to unrelated owners on
+    /// another (false sharing). Padding avoids that, at the cost of 128 bytes
+    /// per counter instead of 8.
+    pub fn padded() -> Self {
+        Self::with_padding(true)
+    }
+
+    fn with_padding(padded: bool) -> Self {
+        Domain {
+            inner: Arc::new(DomainInner {
+                recycler: SegQueue::new(),
+                padded,
+            }),
+        }
+    }
+
+    /// Wrap the given pointer with an owner in this domain.
+    pub fn own<P: IsPtr + Send + 'static>(&self, ptr: P) -> Own<P, Domain> {
+        Own::new_in(ptr, self.clone())
+    }
+
+    /// Pins the current thread. Domains share the global collector, so this
+    /// is the same as [pin](crate::pin).
+    pub fn pin(&self) -> Guard {
+        crossbeam_epoch::pin()
+    }
+
+    /// Makes sure this domain has at least `n` counters pooled, like
+    /// [reserve](crate::reserve) does for the current thread.
+    ///
+    /// Padded domains never take counters from the thread's cache, so they
+    /// need their own reserve once [forbid_counter_allocation](crate::forbid_counter_allocation)
+    /// is set.
+    /// ```
+    ///# use weakref::Domain;
+    /// let domain = Domain::padded();
+    /// domain.reserve(100);
+    /// weakref::forbid_counter_allocation(true);
+    /// let owners: Vec<_> = (0..100).map(|i| domain.own(Box::new(i))).collect();
+    ///# weakref::forbid_counter_allocation(false);
+    /// ```
+    pub fn reserve(&self, n: usize) {
+        while self.inner.recycler.len() < n {
+            if self.inner.padded {
+                for counter in leak_padded_block() {
+                    self.inner.recycler.push(counter);
+                }
+            } else {
+                self.inner.recycler.push(new_generation_counter());
+            }
+        }
+    }
+
+    /// The number of counters waiting in this domain's pool.
+    pub fn pooled_counters(&self) -> usize {
+        self.inner.recycler.len()
+    }
+}
+
+impl Default for Domain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Domain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Domain")
+            .field("padded", &self.inner.padded)
+            .field("pooled_counters", &self.pooled_counters())
+            .finish_non_exhaustive()
+    }
+}
+
+// SAFETY: Destructors are retired exactly like those of [Epoch]
+unsafe impl Reclaim for Domain {
+    type Guard = Guard;
+
+    fn retire(&self, id: RefId, drop: impl FnOnce() + Send + 'static) {
+        Epoch.retire(id, drop);
+    }
+
+    fn flush(&self) {
+        Epoch.flush();
+    }
+
+    fn take_counter(&self) -> GenerationCounter {
+        if let Some(counter) = self.inner.recycler.pop() {
+            return counter;
+        }
+        if !self.inner.padded {
+            return new_generation_counter();
+        }
+        if counter_allocation_forbidden() {
+            panic!(
+                "Ran out of padded generation counters while allocation is forbidden. Reserve more with Domain::reserve."
+            );
+        }
+        let mut block = leak_padded_block();
+        let counter = block.next().unwrap();
+        for spare in block {
+            self.inner.recycler.push(spare);
+        }
+        counter
+    }
+
+    fn give_counter(&self, counter: GenerationCounter) {
+        self.inner.recycler.push(counter);
+    }
+}
+
+impl Drop for DomainInner {
+    fn drop(&mut self) {
+        while let Some(counter) = self.recycler.pop() {
+            recycle_generation_counter(counter);
+        }
+    }
+}
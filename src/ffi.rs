@@ -0,0 +1,132 @@
+//! A C ABI for holding weak references from C and C++, enabled by the `ffi`
+//! feature. The declarations are in `include/weakref.h`.
+//!
+//! Each owner holds an opaque `void *` payload along with a destructor, which
+//! is called once the owner is dropped and no thread is still pinned. A
+//! [WeakrefRef] is a plain [Ref] (it is `#[repr(C)]`), so C code copies it by
+//! value and may keep it forever. To read the payload, pin the thread with
+//! [weakref_pin], call [weakref_ref_get], and unpin with [weakref_unpin] once
+//! finished with the pointer.
+//!
+//! The functions are exported unmangled, so a C application can link against
+//! any static or dynamic Rust library which depends on this crate with the
+//! `ffi` feature enabled.
+
+use crate::guts::{Own, Ref};
+use core::ffi::c_void;
+use core::ptr;
+use crossbeam_epoch::{Guard, pin};
+
+/// The destructor for a payload, called with the data pointer.
+pub type WeakrefDestroy = Option<unsafe extern "C" fn(data: *mut c_void)>;
+
+/// The value behind every owner created through the C ABI.
+#[repr(C)]
+pub struct WeakrefPayload {
+    data: *mut c_void,
+    destroy: WeakrefDestroy,
+}
+
+// SAFETY: The header requires the payload and its destructor to be usable from
+// any thread, since readers and the destructor may run anywhere.
+unsafe impl Send for WeakrefPayload {}
+unsafe impl Sync for WeakrefPayload {}
+
+impl Drop for WeakrefPayload {
+    fn drop(&mut self) {
+        if let Some(destroy) = self.destroy {
+            // SAFETY: The destructor was provided along with the data pointer
+            unsafe { destroy(self.data) };
+        }
+    }
+}
+
+/// An owner created by [weakref_own_new], seen from C as `weakref_own_t *`.
+pub type WeakrefOwn = Own<Box<WeakrefPayload>>;
+
+/// A reference to a payload, seen from C as `weakref_ref_t`.
+pub type WeakrefRef = Ref<WeakrefPayload>;
+
+/// Creates an owner for the payload. Never returns null.
+#[unsafe(no_mangle)]
+pub extern "C" fn weakref_own_new(data: *mut c_void, destroy: WeakrefDestroy) -> *mut WeakrefOwn {
+    let own = Own::new_box(WeakrefPayload { data, destroy });
+    Box::into_raw(Box::new(own))
+}
+
+/// Provides a reference to the owner's payload.
+///
+/// # Safety
+/// `own` must have come from [weakref_own_new] and not been dropped.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn weakref_own_refer(own: *const WeakrefOwn) -> WeakrefRef {
+    // SAFETY: Guaranteed by the caller
+    unsafe { (*own).refer() }
+}
+
+/// Drops the owner, killing every reference. The destructor runs once no
+/// thread can still be reading the payload. Does nothing if `own` is null.
+///
+/// # Safety
+/// `own` must be null, or have come from [weakref_own_new] and not been dropped.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn weakref_own_drop(own: *mut WeakrefOwn) {
+    if !own.is_null() {
+        // SAFETY: Guaranteed by the caller
+        drop(unsafe { Box::from_raw(own) });
+    }
+}
+
+/// Returns a reference which is never alive.
+#[unsafe(no_mangle)]
+pub extern "C" fn weakref_ref_null() -> WeakrefRef {
+    Ref::null()
+}
+
+/// Returns true if the owner has not been dropped. See [Ref::is_alive].
+#[unsafe(no_mangle)]
+pub extern "C" fn weakref_ref_is_alive(weak: WeakrefRef) -> bool {
+    weak.is_alive()
+}
+
+/// Pins the current thread. Never returns null.
+#[unsafe(no_mangle)]
+pub extern "C" fn weakref_pin() -> *mut Guard {
+    Box::into_raw(Box::new(pin()))
+}
+
+/// Unpins the current thread. Does nothing if `guard` is null.
+///
+/// # Safety
+/// `guard` must be null, or have come from [weakref_pin] on the same thread
+/// and not been unpinned.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn weakref_unpin(guard: *mut Guard) {
+    if !guard.is_null() {
+        // SAFETY: Guaranteed by the caller
+        drop(unsafe { Box::from_raw(guard) });
+    }
+}
+
+/// Tries to run any destructors which have become safe, including those
+/// deferred by the current thread. Otherwise they may wait until the thread
+/// has deferred enough of them.
+#[unsafe(no_mangle)]
+pub extern "C" fn weakref_flush() {
+    pin().flush();
+}
+
+/// Provides the payload's data pointer if the owner is alive, or null if it
+/// was dropped. The payload is not destroyed until the guard is unpinned.
+///
+/// # Safety
+/// `guard` must have come from [weakref_pin] on the same thread and not been unpinned.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn weakref_ref_get(weak: WeakrefRef, guard: *const Guard) -> *mut c_void {
+    // SAFETY: Guaranteed by the caller
+    let guard = unsafe { &*guard };
+    match weak.get(guard) {
+        Some(payload) => payload.data,
+        None => ptr::null_mut(),
+    }
+}
@@ -0,0 +1,16 @@
+use crate::ffi::{WeakrefPayload, WeakrefRef};
+use core::mem::offset_of;
+
+/// The layout of `weakref_ref_t` in include/weakref.h.
+#[test]
+fn ffi_ref_layout() {
+    assert_eq!(size_of::<WeakrefRef>(), 3 * size_of::<usize>());
+    assert_eq!(align_of::<WeakrefRef>(), align_of::<usize>());
+    assert_eq!(offset_of!(WeakrefRef, current_gen), 0);
+    assert_eq!(offset_of!(WeakrefRef, expected_gen), size_of::<usize>());
+    assert_eq!(offset_of!(WeakrefRef, pointer), 2 * size_of::<usize>());
+    assert_eq!(
+        size_of::<Option<core::ptr::NonNull<WeakrefPayload>>>(),
+        size_of::<usize>()
+    );
+}
@@ -1,62 +1,251 @@
+use crate::notify::{generation_advanced, generation_revoked};
+use crate::reclaim::{Epoch, IsGuard, Reclaim};
+use crate::stats;
+use core::marker::PhantomData;
 use core::ops::Deref;
 use core::ptr::NonNull;
-use crossbeam_epoch::{Guard, pin};
+use crossbeam_epoch::Guard;
 use crossbeam_queue::SegQueue;
-use std::{cell::RefCell, mem::ManuallyDrop};
+use std::{
+    cell::RefCell,
+    mem::{ManuallyDrop, MaybeUninit},
+};
 
 #[cfg(not(loom))]
 use core::sync::atomic::{AtomicUsize, Ordering};
 #[cfg(loom)]
 use loom::sync::atomic::{AtomicUsize, Ordering};
 
-type GenerationCounter = &'static AtomicUsize;
+pub(crate) type GenerationCounter = &'static AtomicUsize;
 static GLOBAL_RECYCLER: SegQueue<[GenerationCounter; BLOCK_SIZE]> = SegQueue::new();
+/// Counters from partial blocks, left behind by threads which exited.
+static GLOBAL_LEFTOVERS: SegQueue<GenerationCounter> = SegQueue::new();
 thread_local! {
-    static LOCAL_RECYCLER: RefCell<Vec<GenerationCounter>> = RefCell::new(Vec::with_capacity(BLOCK_SIZE*2));
+    static LOCAL_RECYCLER: RefCell<LocalRecycler> = RefCell::new(LocalRecycler {
+        counters: Vec::with_capacity(BLOCK_SIZE*2),
+        forbid_allocation: false,
+    });
 }
 
 #[cfg(loom)]
-const BLOCK_SIZE: usize = 16;
+pub(crate) const BLOCK_SIZE: usize = 16;
 #[cfg(not(loom))]
-const BLOCK_SIZE: usize = 256;
+pub(crate) const BLOCK_SIZE: usize = 256;
+
+/// The pool of counters for a single thread, which gives them back to the
+/// global pool when the thread exits.
+struct LocalRecycler {
+    counters: Vec<GenerationCounter>,
+    /// Set by [forbid_counter_allocation].
+    forbid_allocation: bool,
+}
+
+impl Drop for LocalRecycler {
+    fn drop(&mut self) {
+        flush_counters(&mut self.counters);
+    }
+}
+
+/// Moves every counter to the global pool, in whole blocks where possible.
+fn flush_counters(counters: &mut Vec<GenerationCounter>) {
+    while counters.len() >= BLOCK_SIZE {
+        let block: [GenerationCounter; BLOCK_SIZE] =
+            std::array::from_fn(|_| counters.pop().unwrap());
+        GLOBAL_RECYCLER.push(block);
+    }
+    for counter in counters.drain(..) {
+        GLOBAL_LEFTOVERS.push(counter);
+    }
+}
+
+/// Adds at least one counter to the pool, preferring the global pool over
+/// leaking a new block.
+fn refill_counters(counters: &mut Vec<GenerationCounter>) {
+    if let Some(block) = GLOBAL_RECYCLER.pop() {
+        counters.extend(block);
+        return;
+    }
+    let len = counters.len();
+    counters.extend(core::iter::from_fn(|| GLOBAL_LEFTOVERS.pop()).take(BLOCK_SIZE));
+    if counters.len() > len {
+        return;
+    }
+    let block: [AtomicUsize; BLOCK_SIZE] = std::array::from_fn(|_| AtomicUsize::new(0));
+    let block = Box::leak(Box::new(block));
+    stats::block_leaked(BLOCK_SIZE);
+    #[cfg(not(loom))]
+    crate::compact::register_block(&block[0], BLOCK_SIZE, size_of::<AtomicUsize>());
+    counters.extend(block.iter());
+}
+
+/// A counter which sits alone on its cache line, so that killing one owner
+/// never slows down access to an unrelated one. Some CPUs prefetch cache lines
+/// in pairs, so this pads to 128 bytes rather than 64.
+#[repr(align(128))]
+struct PaddedCounter(AtomicUsize);
+
+const PADDED_BLOCK_SIZE: usize = 32;
+
+/// Leaks a block of [PaddedCounter]s. They are 16 times larger than usual,
+/// so this is only used by padded [Domain](crate::Domain)s.
+pub(crate) fn leak_padded_block() -> impl Iterator<Item = GenerationCounter> {
+    let block: [PaddedCounter; PADDED_BLOCK_SIZE] =
+        std::array::from_fn(|_| PaddedCounter(AtomicUsize::new(0)));
+    let block = Box::leak(Box::new(block));
+    stats::block_leaked(PADDED_BLOCK_SIZE);
+    #[cfg(not(loom))]
+    crate::compact::register_block(&block[0].0, PADDED_BLOCK_SIZE, size_of::<PaddedCounter>());
+    block.iter().map(|padded| &padded.0)
+}
 
 pub(crate) fn new_generation_counter() -> GenerationCounter {
-    LOCAL_RECYCLER.with_borrow_mut(|local_recycler| {
-        if let Some(counter) = local_recycler.pop() {
-            return counter;
-        }
-        if let Some(block) = GLOBAL_RECYCLER.pop() {
-            let [next, rest @ ..] = block;
-            local_recycler.extend(rest);
-            return next;
-        }
-        let block: [AtomicUsize; BLOCK_SIZE] = std::array::from_fn(|_| AtomicUsize::new(0));
-        let block = Box::leak(Box::new(block));
-        local_recycler.extend(block.iter());
-        local_recycler.pop().unwrap()
-    })
+    LOCAL_RECYCLER
+        .try_with(|local_recycler| {
+            let local_recycler = &mut *local_recycler.borrow_mut();
+            let counters = &mut local_recycler.counters;
+            if counters.is_empty() {
+                if local_recycler.forbid_allocation {
+                    panic!("Ran out of generation counters while allocation is forbidden. Reserve more with weakref::reserve.");
+                }
+                refill_counters(counters);
+            }
+            let counter = counters.pop().unwrap();
+            stats::set_local_pooled(counters.len());
+            counter
+        })
+        .unwrap_or_else(|_| {
+            // The thread is exiting, so anything taken from the global pool goes straight back.
+            let mut counters = Vec::new();
+            refill_counters(&mut counters);
+            let counter = counters.pop().unwrap();
+            flush_counters(&mut counters);
+            counter
+        })
 }
 
 pub(crate) fn recycle_generation_counter(counter: GenerationCounter) {
-    LOCAL_RECYCLER.with_borrow_mut(|local_recycler| {
-        if local_recycler.len() == local_recycler.capacity() {
+    let recycled = LOCAL_RECYCLER.try_with(|local_recycler| {
+        let counters = &mut local_recycler.borrow_mut().counters;
+        if counters.len() == counters.capacity() {
             let block: [GenerationCounter; BLOCK_SIZE] =
-                std::array::from_fn(|_| local_recycler.pop().unwrap());
+                std::array::from_fn(|_| counters.pop().unwrap());
             GLOBAL_RECYCLER.push(block);
         }
-        local_recycler.push(counter);
+        counters.push(counter);
+        stats::set_local_pooled(counters.len());
+    });
+    if recycled.is_err() {
+        GLOBAL_LEFTOVERS.push(counter);
+    }
+}
+
+/// Makes sure the current thread has at least `n` generation counters cached,
+/// so that creating that many owners will not need to touch the global pool
+/// or allocate.
+///
+/// Use with [forbid_counter_allocation] to be sure the reserve is large enough.
+/// ```
+/// weakref::reserve(1000);
+/// weakref::forbid_counter_allocation(true);
+/// let owners: Vec<_> = (0..1000).map(weakref::Own::new_box).collect();
+///# weakref::forbid_counter_allocation(false);
+/// ```
+pub fn reserve(n: usize) {
+    LOCAL_RECYCLER.with_borrow_mut(|local_recycler| {
+        let counters = &mut local_recycler.counters;
+        // Leave room for counters to be recycled without spilling the reserve.
+        let capacity = n + BLOCK_SIZE * 2;
+        if counters.capacity() < capacity {
+            counters.reserve(capacity - counters.len());
+        }
+        while counters.len() < n {
+            refill_counters(counters);
+        }
+        stats::set_local_pooled(counters.len());
     })
 }
 
+/// While `forbid` is set, panic instead of refilling the counter cache of the
+/// current thread. Creating an owner then never touches the global pool or
+/// allocates a new block of counters, as long as enough were [reserved](reserve).
+///
+/// This is meant for real-time threads, to fail loudly when the reserve runs
+/// dry instead of stalling.
+pub fn forbid_counter_allocation(forbid: bool) {
+    LOCAL_RECYCLER.with_borrow_mut(|local_recycler| local_recycler.forbid_allocation = forbid);
+}
+
+/// Whether [forbid_counter_allocation] is set on the current thread.
+pub(crate) fn counter_allocation_forbidden() -> bool {
+    LOCAL_RECYCLER
+        .try_with(|local_recycler| local_recycler.borrow().forbid_allocation)
+        .unwrap_or(false)
+}
+
+/// Moves every generation counter cached by the current thread to the global
+/// pool, so that other threads can reuse them.
+///
+/// This happens automatically when a thread exits. But threads which stop
+/// creating owners for a long time may want to give back their cache early.
+/// ```
+/// let data = weakref::Own::new_box(42);
+/// drop(data);
+/// weakref::flush_thread_cache();
+/// ```
+pub fn flush_thread_cache() {
+    let _ = LOCAL_RECYCLER.try_with(|local_recycler| {
+        flush_counters(&mut local_recycler.borrow_mut().counters);
+        stats::set_local_pooled(0);
+    });
+}
+
+/// Increments the generation counter with Release ordering so that no
+/// [Ref::get] can access the pointer from now on, and wakes anything waiting
+/// for that. Returns false if the counter had already moved past `expected_gen`.
+pub(crate) fn advance_generation(counter: GenerationCounter, expected_gen: usize) -> bool {
+    let advanced = increment_generation(counter, expected_gen);
+    if advanced {
+        generation_advanced(counter, expected_gen);
+    }
+    advanced
+}
+
+fn increment_generation(counter: GenerationCounter, expected_gen: usize) -> bool {
+    // SeqCst pairs with the waiter count in `notify`
+    counter
+        .compare_exchange(
+            expected_gen,
+            expected_gen + 1,
+            Ordering::SeqCst,
+            Ordering::Relaxed,
+        )
+        .is_ok()
+}
+
+/// Checks if a counter at `generation` can be handed to another owner, which
+/// needs to be able to kill it one more time. Otherwise the counter is
+/// completely unusable and must be leaked forever, and whoever drops it
+/// counts it with [stats::counter_retired]. Generations stop at `u32::MAX`
+/// rather than `usize::MAX`, so that they always fit in a [CompactRef](crate::CompactRef).
+pub(crate) fn can_reuse(generation: usize) -> bool {
+    generation < u32::MAX as usize
+}
+
+pub(crate) fn global_pooled() -> usize {
+    GLOBAL_RECYCLER.len() * BLOCK_SIZE + GLOBAL_LEFTOVERS.len()
+}
+
 #[allow(unused)]
 pub(crate) fn empty_recycler() {
-    LOCAL_RECYCLER.with_borrow_mut(|r| r.clear());
+    LOCAL_RECYCLER.with_borrow_mut(|r| r.counters.clear());
+    stats::set_local_pooled(0);
     while GLOBAL_RECYCLER.pop().is_some() {}
+    while GLOBAL_LEFTOVERS.pop().is_some() {}
 }
 
 #[cfg(test)]
 pub(crate) fn local_recycler_len() -> usize {
-    LOCAL_RECYCLER.with_borrow(|r| r.len())
+    LOCAL_RECYCLER.with_borrow(|r| r.counters.len())
 }
 
 #[cfg(test)]
@@ -64,6 +253,11 @@ pub(crate) fn global_recycler_len() -> usize {
     GLOBAL_RECYCLER.len()
 }
 
+#[cfg(test)]
+pub(crate) fn global_leftovers_len() -> usize {
+    GLOBAL_LEFTOVERS.len()
+}
+
 /// Implemented for any owning pointer.
 ///
 /// # Safety
@@ -84,8 +278,15 @@ pub trait IsPtr {
 }
 
 /// Unique owner for a value, which will inform references when dropped.
-#[repr(transparent)]
-pub struct Own<P: IsPtr + Send + 'static> {
+///
+/// The destructor is deferred by the reclamation backend `R`, which is
+/// [Epoch] unless created with [Own::new_in].
+///
+/// The owner is `#[repr(C)]`, with its [Ref] first and the backend after it.
+/// So with a zero-sized backend like [Epoch] it has exactly the same size and
+/// alignment as the reference.
+#[repr(C)]
+pub struct Own<P: IsPtr + Send + 'static, R: Reclaim = Epoch> {
     /// The weak reference. _SAFETY: Do not mutate._
     ///
     /// It would be nice to make this public, but there are soundness
@@ -100,94 +301,267 @@ pub struct Own<P: IsPtr + Send + 'static> {
     /// std::thread::spawn(mut || { *a; });
     /// ```
     #[doc(hidden)]
-    pub _weak: Ref<P::T>,
+    pub _weak: Ref<P::T, R::Guard>,
+    reclaim: R,
 }
 
 impl<P: IsPtr + Send + 'static> Own<P> {
     /// Wrap the given pointer so that it can inform weak references when dropped.
     pub fn new(ptr: P) -> Self {
-        Self::new_reuse(new_generation_counter(), ptr)
+        Self::new_in(ptr, Epoch)
     }
 
     /// Like [Own::new], but cheaper if an existing owned needs to be dropped.
     /// The generation counter can be incremented and reused without checking the global pool.
-    pub fn new_from<R: IsPtr + Send + 'static>(ptr: P, other: Own<R>) -> Self {
-        Self::new_reuse(other.kill(&pin()).unwrap(), ptr)
+    pub fn new_from<Q: IsPtr + Send + 'static>(ptr: P, other: Own<Q>) -> Self {
+        Self::new_reuse(other.kill().unwrap(), ptr, Epoch)
+    }
+}
+
+impl<P: IsPtr + Send + 'static, R: Reclaim> Own<P, R> {
+    /// Like [Own::new], but destructors are deferred by the given backend
+    /// instead of [Epoch].
+    /// ```
+    ///# use weakref::{Own, reclaim::{Hazard, HazardGuard, IsGuard}};
+    /// let data = Own::new_in(Box::new(42), Hazard);
+    /// let weak = data.refer();
+    /// assert_eq!(weak.get(&HazardGuard::pin()), Some(&42));
+    /// ```
+    pub fn new_in(ptr: P, reclaim: R) -> Self {
+        Self::new_reuse(reclaim.take_counter(), ptr, reclaim)
     }
 
     /// Provides the weak pointer.
-    pub fn refer(&self) -> Ref<P::T> {
+    pub fn refer(&self) -> Ref<P::T, R::Guard> {
         self._weak
     }
 
-    fn new_reuse(current_gen: GenerationCounter, ptr: P) -> Self {
+    /// Kills every reference provided so far, but keeps the value alive and
+    /// owned. Returns a fresh reference for the new generation, which
+    /// [Own::refer] will also provide from now on.
+    ///
+    /// Threads which already got the value from an old reference can keep
+    /// reading it until they are unpinned. [Own::on_kill] callbacks carry over
+    /// to the new generation, but the [id](Own::id) changes, so entries keyed
+    /// by the old id in maps and sets no longer match the owner.
+    /// ```
+    ///# use weakref::{Own, pin};
+    /// let mut data = Own::new_box(42);
+    /// let old = data.refer();
+    /// let new = data.revoke();
+    /// assert_eq!(old.get(&pin()), None);
+    /// assert_eq!(new.get(&pin()), Some(&42));
+    /// ```
+    pub fn revoke(&mut self) -> Ref<P::T, R::Guard> {
+        let old = self._weak;
+        if !increment_generation(old.current_gen, old.expected_gen) {
+            panic!("Tried to revoke a dead reference. Did you mutate Own._weak?");
+        }
+        if can_reuse(old.expected_gen + 1) {
+            self._weak.expected_gen += 1;
+        } else {
+            // Leave the old counter behind, since we could never kill it again.
+            stats::counter_retired();
+            let current_gen = self.reclaim.take_counter();
+            self._weak.current_gen = current_gen;
+            self._weak.expected_gen = current_gen.load(Ordering::Acquire);
+        }
+        generation_revoked(
+            old.current_gen,
+            old.expected_gen,
+            self._weak.current_gen,
+            self._weak.expected_gen,
+        );
+        self._weak
+    }
+
+    /// Identifies this owner. Equal to [Ref::id] for all of its references.
+    ///
+    /// The id only lasts until [Own::revoke], which gives the owner a new one
+    /// along with its new references.
+    /// ```
+    ///# use weakref::Own;
+    /// let mut data = Own::new_box(42);
+    /// let old = data.id();
+    /// data.revoke();
+    /// assert_ne!(data.id(), old);
+    /// ```
+    pub fn id(&self) -> RefId {
+        self._weak.id()
+    }
+
+    fn new_reuse(current_gen: GenerationCounter, ptr: P, reclaim: R) -> Self {
         let pointer = Some(P::into_raw_ptr(ptr));
         let expected_gen = current_gen.load(Ordering::Acquire);
+        stats::own_created();
         Own {
             _weak: Ref {
                 current_gen,
                 expected_gen,
                 pointer,
+                _guard: PhantomData,
             },
+            reclaim,
         }
     }
 
-    fn kill(self, guard: &Guard) -> Option<GenerationCounter> {
+    fn kill(self) -> Option<GenerationCounter> {
         let mut this = ManuallyDrop::new(self);
         // SAFETY: self is moved into ManuallyDrop, preventing double-drop
-        unsafe { this.kill_mut(guard) }
+        let counter = unsafe { this.kill_mut() };
+        // SAFETY: The backend is not used again
+        drop(unsafe { core::ptr::read(&this.reclaim) });
+        counter
+    }
+
+    /// # Safety
+    /// Absolutely no use of `self` is permitted after calling this function,
+    /// except to drop the backend.
+    unsafe fn kill_mut(&mut self) -> Option<GenerationCounter> {
+        let id = self._weak.id();
+        // SAFETY: Forwarded to the caller
+        let (ptr, counter) = unsafe { self.take_mut() };
+
+        // Send the object to be dropped. If a load has already occurred and the
+        // pointer is running around somewhere, the cleanup will be deferred until
+        // that thread is unpinned. Otherwise it may occur immediately.
+        self.reclaim.retire(id, move || drop(ptr));
+        counter
+    }
+
+    /// Like [Own::kill], but provides the pointer instead of dropping it.
+    ///
+    /// Other threads may still be reading through the pointer until they are
+    /// unpinned, so it must not be dropped or mutated before then.
+    pub(crate) fn take(self) -> (P, Option<GenerationCounter>) {
+        let mut this = ManuallyDrop::new(self);
+        // SAFETY: self is moved into ManuallyDrop, preventing double-drop
+        let taken = unsafe { this.take_mut() };
+        // SAFETY: The backend is not used again
+        drop(unsafe { core::ptr::read(&this.reclaim) });
+        taken
     }
 
     /// # Safety
     /// Absolutely no use of `self` is permitted after calling this function,
     /// even to drop it.
-    unsafe fn kill_mut(&mut self, guard: &Guard) -> Option<GenerationCounter> {
+    unsafe fn take_mut(&mut self) -> (P, Option<GenerationCounter>) {
         // Increment the generation counter with Release ordering so that no
-        // [Ref::get] can access the pointer from now on. If a load has already
-        // occurred and the pointer is running around somewhere, the cleanup
-        // will be deferred until that thread is unpinned. Otherwise it may occur
-        // immediately.
-        let new_gen = self._weak.expected_gen + 1;
-        if self
-            ._weak
-            .current_gen
-            .compare_exchange(
-                self._weak.expected_gen,
-                new_gen,
-                Ordering::AcqRel,
-                Ordering::Relaxed,
-            )
-            .is_err()
-        {
+        // [Ref::get] can access the pointer from now on.
+        if !advance_generation(self._weak.current_gen, self._weak.expected_gen) {
             panic!("Tried to drop a dead reference. Did you mutate Own._weak?");
         }
-
-        // Send the object to be dropped.
+        stats::own_killed();
+        // SAFETY: Pointer was returned by into_raw_ptr in Own::new_reuse
         let ptr = unsafe { P::from_raw_ptr(self._weak.pointer.take().unwrap()) };
-        guard.defer(move || drop(ptr));
 
         // Recycle the generation counter, so long as it is possible to kill one more time.
-        // Otherwise leak it forever, since it is completely unusable. This should
-        // never happen in practice.
-        if new_gen != usize::MAX {
-            Some(self._weak.current_gen)
+        if can_reuse(self._weak.expected_gen + 1) {
+            (ptr, Some(self._weak.current_gen))
         } else {
-            None
+            stats::counter_retired();
+            (ptr, None)
         }
     }
 }
 
-impl<P: IsPtr + Send + 'static> Drop for Own<P> {
+impl<T: Send + 'static> Own<Box<T>> {
+    /// Like [Own::new_box], but the value can hold a reference to itself, as
+    /// with [Arc::new_cyclic](std::sync::Arc::new_cyclic).
+    ///
+    /// The reference given to `build` cannot be read until it returns, then
+    /// becomes alive along with the owner. Until then [Ref::is_alive] is
+    /// false, but the reference is not dead yet: [Ref::until_dead] and
+    /// [Ref::wait_dead] wait for the owner to be dropped. If `build` panics,
+    /// the reference dies for good, waking any waiters, and the generation
+    /// counter is recycled.
+    /// ```
+    ///# use weakref::{Own, Ref, pin};
+    /// struct Node {
+    ///     me: Ref<Node>,
+    /// }
+    ///
+    /// let node = Own::new_cyclic(|me| {
+    ///     assert!(!me.is_alive());
+    ///     Node { me }
+    /// });
+    /// assert!(node.me.same_owner(node.refer()));
+    /// assert!(node.me.get(&pin()).is_some());
+    /// ```
+    pub fn new_cyclic(build: impl FnOnce(Ref<T>) -> T) -> Self {
+        /// Kills the reference given to `build` for good if it panics.
+        struct Abort<T> {
+            current_gen: GenerationCounter,
+            generation: usize,
+            raw: *mut MaybeUninit<T>,
+        }
+
+        impl<T> Drop for Abort<T> {
+            fn drop(&mut self) {
+                // Skip over the generation expected by the reference, so that
+                // it never matches even after the counter is reused. Going
+                // through it with advance_generation would briefly make the
+                // uninitialized value reachable, so wake the waiters by hand.
+                let dead_gen = self.generation + 2;
+                // SeqCst pairs with the waiter count in `notify`
+                self.current_gen.store(dead_gen, Ordering::SeqCst);
+                generation_advanced(self.current_gen, self.generation + 1);
+                if can_reuse(dead_gen) {
+                    recycle_generation_counter(self.current_gen);
+                } else {
+                    stats::counter_retired();
+                }
+                // SAFETY: The box was never initialized or handed out
+                drop(unsafe { Box::from_raw(self.raw) });
+            }
+        }
+
+        let (current_gen, generation) = loop {
+            let current_gen = new_generation_counter();
+            let generation = current_gen.load(Ordering::Acquire);
+            // The owner starts one generation ahead, and must still be killable.
+            if can_reuse(generation + 1) {
+                break (current_gen, generation);
+            }
+            stats::counter_retired();
+        };
+        let raw = Box::into_raw(Box::<T>::new_uninit());
+        let abort = Abort {
+            current_gen,
+            generation,
+            raw,
+        };
+        let me = Ref {
+            current_gen,
+            expected_gen: generation + 1,
+            pointer: NonNull::new(raw.cast::<T>()),
+            _guard: PhantomData,
+        };
+
+        let value = build(me);
+        let abort = ManuallyDrop::new(abort);
+        // SAFETY: The box is still uniquely owned
+        unsafe { abort.raw.write(MaybeUninit::new(value)) };
+        // Release ordering publishes the value to every reference
+        current_gen.store(generation + 1, Ordering::Release);
+        stats::own_created();
+        Own {
+            _weak: me,
+            reclaim: Epoch,
+        }
+    }
+}
+
+impl<P: IsPtr + Send + 'static, R: Reclaim> Drop for Own<P, R> {
     fn drop(&mut self) {
-        let guard = pin();
         // SAFETY: Called from Drop::drop, so self will never be used again
-        if let Some(counter) = unsafe { self.kill_mut(&guard) } {
-            recycle_generation_counter(counter);
+        if let Some(counter) = unsafe { self.kill_mut() } {
+            self.reclaim.give_counter(counter);
         }
     }
 }
 
-impl<P: IsPtr + Send + 'static> Deref for Own<P> {
+impl<P: IsPtr + Send + 'static, R: Reclaim> Deref for Own<P, R> {
     type Target = P::T;
 
     fn deref(&self) -> &Self::Target {
@@ -197,30 +571,46 @@ impl<P: IsPtr + Send + 'static> Deref for Own<P> {
     }
 }
 
-unsafe impl<P: IsPtr + Send> Send for Own<P> where P::T: Sync {}
-unsafe impl<P: IsPtr + Send> Sync for Own<P> where P::T: Sync {}
+unsafe impl<P: IsPtr + Send, R: Reclaim> Send for Own<P, R> where P::T: Sync {}
+unsafe impl<P: IsPtr + Send, R: Reclaim> Sync for Own<P, R> where P::T: Sync {}
 
 /// Weak reference for a value which checks liveness at runtime.
+///
+/// The guard type `G` comes from the [Reclaim] backend of the owner, and is
+/// the crossbeam [Guard] by default.
 #[repr(C)]
-pub struct Ref<T: ?Sized> {
+pub struct Ref<T: ?Sized, G = Guard> {
     /// This Ref is only alive if the generation numbers match.
-    current_gen: GenerationCounter,
-    expected_gen: usize,
-    pointer: Option<NonNull<T>>,
+    pub(crate) current_gen: GenerationCounter,
+    pub(crate) expected_gen: usize,
+    pub(crate) pointer: Option<NonNull<T>>,
+    pub(crate) _guard: PhantomData<fn(&G)>,
+}
+
+/// The identity of an owner, for use as a key in maps and sets.
+///
+/// Generation counters are never freed and never return to a previous
+/// generation. So once the owner is dropped, its id is never handed out again,
+/// even when the counter is reused. The same goes for the old id of an owner
+/// which was [revoked](Own::revoke).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct RefId {
+    pub(crate) counter: usize,
+    generation: usize,
 }
 
-unsafe impl<T: Sync + ?Sized> Send for Ref<T> {}
-unsafe impl<T: Sync + ?Sized> Sync for Ref<T> {}
+unsafe impl<T: Sync + ?Sized, G> Send for Ref<T, G> {}
+unsafe impl<T: Sync + ?Sized, G> Sync for Ref<T, G> {}
 
-impl<T: ?Sized> Clone for Ref<T> {
+impl<T: ?Sized, G> Clone for Ref<T, G> {
     fn clone(&self) -> Self {
         *self
     }
 }
 
-impl<T: ?Sized> Copy for Ref<T> {}
+impl<T: ?Sized, G> Copy for Ref<T, G> {}
 
-impl<T: ?Sized> Ref<T> {
+impl<T: ?Sized, G: IsGuard> Ref<T, G> {
     /// Check if the original owner has been dropped. If it is alive, return the reference.
     ///
     /// __The [Ref::get] method is the point of the weakref crate__
@@ -236,7 +626,8 @@ impl<T: ?Sized> Ref<T> {
     ///
     /// Notice that the returned reference only borrows from [Guard]. Until the thread is unpinned,
     /// the generation counter does not need to be re-checked.
-    pub fn get(self, _guard: &Guard) -> Option<&T> {
+    pub fn get(self, guard: &G) -> Option<&T> {
+        guard.protect(self.id());
         // Acquire ordering ensures we see the latest generation - if it matches,
         // the epoch guard prevents the pointer from being freed
         let current_gen = self.current_gen.load(Ordering::Acquire);
@@ -247,9 +638,9 @@ impl<T: ?Sized> Ref<T> {
         }
     }
 
-    /// [Pin](pin) the current thread and check if the owner has been dropped. If it is alive, call `func` and return the output.
+    /// [Pin](IsGuard::pin) the current thread and check if the owner has been dropped. If it is alive, call `func` and return the output.
     pub fn inspect<O>(self, func: impl FnOnce(&T) -> O) -> Option<O> {
-        self.get(&pin()).map(func)
+        self.get(&G::pin()).map(func)
     }
 
     /// Produces a new weak reference tied to self, which points to something reachable through the original pointer.
@@ -261,12 +652,12 @@ impl<T: ?Sized> Ref<T> {
     /// drop(list);
     /// assert_eq!(elem.get(&pin()), None);
     /// ```
-    pub fn map<R: ?Sized>(self, func: impl FnOnce(&T) -> &R) -> Ref<R> {
-        self.map_with(func, &pin())
+    pub fn map<R: ?Sized>(self, func: impl FnOnce(&T) -> &R) -> Ref<R, G> {
+        self.map_with(func, &G::pin())
     }
 
     /// Like [Ref::map], but cheaper if a thread guard is already available.
-    pub fn map_with<R: ?Sized>(&self, func: impl FnOnce(&T) -> &R, guard: &Guard) -> Ref<R> {
+    pub fn map_with<R: ?Sized>(&self, func: impl FnOnce(&T) -> &R, guard: &G) -> Ref<R, G> {
         Ref {
             current_gen: self.current_gen,
             expected_gen: self.expected_gen,
@@ -274,6 +665,7 @@ impl<T: ?Sized> Ref<T> {
                 Some(value) => Some(NonNull::from_ref(func(value))),
                 None => None,
             },
+            _guard: PhantomData,
         }
     }
 
@@ -284,16 +676,16 @@ impl<T: ?Sized> Ref<T> {
     /// let elem: Ref<i32> = list.refer().filter_map(|x| x.get(100));
     /// assert_eq!(elem.get(&pin()), None);
     /// ```
-    pub fn filter_map<R: ?Sized>(self, func: impl FnOnce(&T) -> Option<&R>) -> Ref<R> {
-        self.filter_map_with(func, &pin())
+    pub fn filter_map<R: ?Sized>(self, func: impl FnOnce(&T) -> Option<&R>) -> Ref<R, G> {
+        self.filter_map_with(func, &G::pin())
     }
 
     /// Like [Ref::map], but cheaper if a thread guard is already available.
     pub fn filter_map_with<R: ?Sized>(
         &self,
         func: impl FnOnce(&T) -> Option<&R>,
-        guard: &Guard,
-    ) -> Ref<R> {
+        guard: &G,
+    ) -> Ref<R, G> {
         Ref {
             current_gen: self.current_gen,
             expected_gen: self.expected_gen,
@@ -301,9 +693,12 @@ impl<T: ?Sized> Ref<T> {
                 Some(value) => func(value).map(NonNull::from_ref),
                 None => None,
             },
+            _guard: PhantomData,
         }
     }
+}
 
+impl<T: ?Sized, G> Ref<T, G> {
     /// Checks whether the owner has been dopped.
     ///
     /// Be aware there are no ordering guarentees on this function. If true
@@ -315,6 +710,11 @@ impl<T: ?Sized> Ref<T> {
         current_gen == self.expected_gen && self.pointer.is_some()
     }
 
+    /// Like [Ref::is_alive], but ignores whether the pointer is null.
+    pub(crate) fn is_owner_alive(&self) -> bool {
+        self.current_gen.load(Ordering::Relaxed) == self.expected_gen
+    }
+
     /// Returns true if this reference is exactly [Ref::null].
     ///
     /// Note [Ref::map] and [Ref::filter_map] will return null when called on a dead
@@ -325,6 +725,30 @@ impl<T: ?Sized> Ref<T> {
         self.pointer.is_none()
     }
 
+    /// Identifies the owner of this reference, ignoring where it points.
+    ///
+    /// References produced by [Ref::map] share the id of the original. So do
+    /// all references allocated in the same [Region](crate::Region).
+    /// ```
+    ///# use weakref::{Own, pin};
+    /// let list = Own::new(vec![1, 2, 3]);
+    /// let elem = list.refer().map(|x| &x[1]);
+    /// assert_eq!(elem.id(), list.id());
+    /// assert_ne!(elem.id(), Own::new_box(2).id());
+    /// ```
+    pub fn id(&self) -> RefId {
+        RefId {
+            counter: self.current_gen as *const AtomicUsize as usize,
+            generation: self.expected_gen,
+        }
+    }
+
+    /// Returns true if both references have the same owner, even if they point
+    /// to different places or have different types.
+    pub fn same_owner<R: ?Sized, H>(&self, other: Ref<R, H>) -> bool {
+        self.id() == other.id()
+    }
+
     /// Returns a fake reference where [Ref::get] is always None, as if the owner was dropped.
     /// ```
     ///# use weakref::{Ref, pin};
@@ -338,6 +762,7 @@ impl<T: ?Sized> Ref<T> {
             current_gen: &STATIC_GEN,
             expected_gen: 0,
             pointer: None,
+            _guard: PhantomData,
         }
     }
 }
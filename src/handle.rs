@@ -0,0 +1,185 @@
+use crate::arena::Arena;
+use crate::guts::Ref;
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
+use crossbeam_epoch::Guard;
+use std::fmt;
+
+/// A plain-data handle to a value in a [HandleTable], which checks liveness at runtime.
+///
+/// Handles are 8 bytes with a stable `#[repr(C)]` layout (a `u32` slot index
+/// followed by a `u32` generation) and contain no pointers, so they can be
+/// handed to scripting languages or stored in ECS components. Resolving a
+/// handle compares its generation against the slot, exactly like [Ref::get]
+/// does against the generation counter, so a handle never resolves to a value
+/// inserted after its own was removed.
+#[repr(C)]
+pub struct Handle<T> {
+    index: u32,
+    generation: u32,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    /// Packs the handle into an integer, with the index in the high half.
+    pub fn to_bits(self) -> u64 {
+        (self.index as u64) << 32 | self.generation as u64
+    }
+
+    /// Unpacks a handle from [Handle::to_bits]. Any value is safe to resolve,
+    /// but garbage will most likely be dead.
+    pub fn from_bits(bits: u64) -> Self {
+        Handle {
+            index: (bits >> 32) as u32,
+            generation: bits as u32,
+            _value: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_bits() == other.to_bits()
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.to_bits().hash(state)
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("index", &self.index)
+            .field("generation", &self.generation)
+            .finish()
+    }
+}
+
+/// An [Arena] which hands out plain-data [Handle]s instead of pointers.
+///
+/// Handles from a different table may resolve to an unrelated value, but
+/// never to memory which has been freed.
+///
+/// ```
+///# use weakref::{HandleTable, pin};
+/// let mut table = HandleTable::new();
+/// let a = table.insert("a");
+/// let b = table.insert("b");
+/// assert_eq!(size_of_val(&a), 8);
+/// assert_eq!(table.get(a, &pin()), Some(&"a"));
+///
+/// assert!(table.remove(a));
+/// assert_eq!(table.get(a, &pin()), None);
+/// assert_eq!(table.get(b, &pin()), Some(&"b"));
+/// ```
+pub struct HandleTable<T: Send + 'static> {
+    arena: Arena<T>,
+}
+
+impl<T: Send + 'static> HandleTable<T> {
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        HandleTable {
+            arena: Arena::new(),
+        }
+    }
+
+    /// The number of values currently in the table.
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    /// Returns true if the table holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.arena.is_empty()
+    }
+
+    /// Moves the value into a vacant slot and provides the handle.
+    pub fn insert(&mut self, value: T) -> Handle<T> {
+        // Check first, so that a panic does not leave the value in the table.
+        if u32::try_from(self.arena.next_index_bound()).is_err() {
+            panic!("too many slots for a handle");
+        }
+        let (index, weak) = self.arena.insert_indexed(value);
+        Handle {
+            index: index as u32,
+            // Generations stop before u32::MAX, see `can_reuse`
+            generation: weak.expected_gen as u32,
+            _value: PhantomData,
+        }
+    }
+
+    /// Returns true if the value is still in the table.
+    pub fn contains(&self, handle: Handle<T>) -> bool {
+        self.find(handle).is_some()
+    }
+
+    fn find(&self, handle: Handle<T>) -> Option<Ref<T>> {
+        self.arena
+            .refer_indexed(handle.index as usize, handle.generation as usize)
+    }
+
+    /// Finds the handle for a reference to a value in this table.
+    pub fn handle_of(&self, weak: Ref<T>) -> Option<Handle<T>> {
+        let index = self.arena.index_of(weak)?;
+        Some(Handle {
+            index: index as u32,
+            generation: weak.expected_gen as u32,
+            _value: PhantomData,
+        })
+    }
+
+    /// Removes the value, killing every handle and reference to it. The value
+    /// is dropped once no thread can still be reading it.
+    ///
+    /// Returns false if the value was already removed.
+    pub fn remove(&mut self, handle: Handle<T>) -> bool {
+        match self.find(handle) {
+            Some(weak) => self.arena.remove(weak),
+            None => false,
+        }
+    }
+}
+
+/// Values are only accessed through a shared table if they are [Sync], since
+/// the table itself is shared between threads whenever the values are [Send].
+impl<T: Send + Sync + 'static> HandleTable<T> {
+    /// Check if the value has been removed. If it is still in the table, return the reference.
+    ///
+    /// Like [Ref::get], the reference only borrows from the guard and stays
+    /// valid even if the value is removed in the meantime.
+    ///
+    /// ```compile_fail
+    ///# use weakref::{HandleTable, pin};
+    /// let mut table = HandleTable::new();
+    /// let handle = table.insert(std::cell::Cell::new(1));
+    /// table.get(handle, &pin());
+    /// ```
+    pub fn get<'g>(&self, handle: Handle<T>, guard: &'g Guard) -> Option<&'g T> {
+        self.find(handle)?.get(guard)
+    }
+
+    /// Provides a weak pointer to the value, if it is still in the table.
+    pub fn refer(&self, handle: Handle<T>) -> Option<Ref<T>> {
+        self.find(handle)
+    }
+}
+
+impl<T: Send + 'static> Default for HandleTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
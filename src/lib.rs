@@ -51,8 +51,43 @@ use std::pin::Pin;
 use std::sync::Arc;
 use std::{fmt, ptr::NonNull};
 
+mod arena;
+#[cfg(not(loom))]
+mod compact;
+mod domain;
+#[cfg(all(feature = "ffi", not(loom)))]
+pub mod ffi;
 mod guts;
-pub use guts::{IsPtr, Own, Ref};
+mod handle;
+mod local;
+mod notify;
+mod rcu;
+pub mod reclaim;
+mod region;
+mod retired;
+mod shared;
+mod stats;
+mod token;
+pub mod weak_key_map;
+pub mod weak_vec;
+pub use arena::Arena;
+#[cfg(not(loom))]
+pub use compact::CompactRef;
+pub use domain::Domain;
+pub use guts::{
+    IsPtr, Own, Ref, RefId, flush_thread_cache, forbid_counter_allocation, reserve,
+};
+pub use handle::{Handle, HandleTable};
+pub use local::{LocalGuard, LocalOwn, LocalRef, local_pin};
+pub use notify::UntilDead;
+pub use rcu::{RcuOwn, RcuRef};
+pub use region::Region;
+pub use retired::Retired;
+pub use shared::SharedOwn;
+pub use stats::{Stats, stats};
+pub use token::TokenTable;
+pub use weak_key_map::WeakKeyMap;
+pub use weak_vec::WeakVec;
 
 /// A guard that allows continued access to a weakref.
 ///
@@ -70,6 +105,8 @@ mod loom_tests;
 mod ui_tests;
 #[cfg(test)]
 mod recycler_tests;
+#[cfg(all(test, feature = "ffi", not(loom)))]
+mod ffi_tests;
 
 impl<T: Send + 'static> Own<Box<T>> {
     /// The standard way to create an `Own<Box<T>> + Ref<T>`.
@@ -80,7 +117,7 @@ impl<T: Send + 'static> Own<Box<T>> {
     }
 }
 
-impl<P: IsPtr + Send> fmt::Debug for Own<P>
+impl<P: IsPtr + Send, R: reclaim::Reclaim> fmt::Debug for Own<P, R>
 where
     P::T: fmt::Debug,
 {
@@ -91,9 +128,9 @@ where
     }
 }
 
-impl<T: fmt::Debug + ?Sized> fmt::Debug for Ref<T> {
+impl<T: fmt::Debug + ?Sized, G: reclaim::IsGuard> fmt::Debug for Ref<T, G> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.get(&pin()) {
+        match self.get(&G::pin()) {
             Some(live) => {
                 // `.field` requires `T: Sized` and `field_with` is unstable
                 // f.debug_tuple("Ref::Live").field(live).finish()
@@ -230,7 +267,7 @@ impl IsPtr for () {
 #[macro_export]
 macro_rules! refer {
     ($owner:expr) => {{
-        let r: $crate::Ref<_> = $owner._weak;
+        let r: $crate::Ref<_, _> = $owner._weak;
         r
     }};
 }
@@ -0,0 +1,281 @@
+use crate::guts::IsPtr;
+use core::any::Any;
+use core::cell::{Cell, RefCell};
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crossbeam_queue::SegQueue;
+use std::fmt;
+
+/// How many counters to leak at once when a thread runs out.
+const LOCAL_BLOCK_SIZE: usize = 64;
+
+/// Only ever accessed from one thread at a time, with Relaxed ordering. It is
+/// atomic so that the counter can move to another thread once its own exits.
+type LocalCounter = &'static AtomicUsize;
+
+/// Counters left over by threads which have exited.
+static EXITED_COUNTERS: SegQueue<LocalCounter> = SegQueue::new();
+
+/// The number of blocks of local counters ever leaked.
+#[cfg(test)]
+pub(crate) static LOCAL_BLOCKS_LEAKED: AtomicUsize = AtomicUsize::new(0);
+
+/// Per-thread state shared by every [LocalOwn] and [LocalGuard].
+struct Local {
+    recycler: RefCell<Vec<LocalCounter>>,
+    /// The number of live [LocalGuard]s on this thread.
+    guards: Cell<usize>,
+    /// Values whose owner was dropped while a guard was alive.
+    pending: RefCell<Vec<Box<dyn Any>>>,
+}
+
+thread_local! {
+    static LOCAL: Local = const {
+        Local {
+            recycler: RefCell::new(Vec::new()),
+            guards: Cell::new(0),
+            pending: RefCell::new(Vec::new()),
+        }
+    };
+}
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        for counter in self.recycler.get_mut().drain(..) {
+            EXITED_COUNTERS.push(counter);
+        }
+    }
+}
+
+fn new_local_counter() -> LocalCounter {
+    LOCAL.with(|local| {
+        let mut recycler = local.recycler.borrow_mut();
+        if let Some(counter) = recycler.pop() {
+            return counter;
+        }
+        recycler.extend((0..LOCAL_BLOCK_SIZE).map_while(|_| EXITED_COUNTERS.pop()));
+        if let Some(counter) = recycler.pop() {
+            return counter;
+        }
+        let block: [AtomicUsize; LOCAL_BLOCK_SIZE] = std::array::from_fn(|_| AtomicUsize::new(0));
+        let block = Box::leak(Box::new(block));
+        crate::stats::block_leaked(LOCAL_BLOCK_SIZE);
+        #[cfg(test)]
+        LOCAL_BLOCKS_LEAKED.fetch_add(1, Ordering::Relaxed);
+        recycler.extend(block.iter());
+        recycler.pop().unwrap()
+    })
+}
+
+/// Checks if a local counter at `generation` can be killed one more time.
+/// Local counters are never compacted, so they can use every generation.
+fn can_reuse_local(generation: usize) -> bool {
+    generation < usize::MAX
+}
+
+/// Marks the current thread as accessing [LocalRef]s.
+///
+/// This is the single-threaded equivalent of [pin](crate::pin), but only costs
+/// a thread-local increment. While any guard is alive, dropped [LocalOwn]s
+/// keep their values until the last guard is dropped.
+pub fn local_pin() -> LocalGuard {
+    LOCAL.with(|local| local.guards.set(local.guards.get() + 1));
+    LocalGuard {
+        _not_send: PhantomData,
+    }
+}
+
+/// A guard that allows continued access to a [LocalRef], created by [local_pin].
+#[must_use]
+pub struct LocalGuard {
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for LocalGuard {
+    fn drop(&mut self) {
+        let _ = LOCAL.try_with(|local| {
+            let guards = local.guards.get() - 1;
+            local.guards.set(guards);
+            if guards != 0 {
+                return;
+            }
+            // Destructors may drop more owners, so keep going until nothing is left.
+            loop {
+                let pending = local.pending.take();
+                if pending.is_empty() {
+                    break;
+                }
+                drop(pending);
+            }
+        });
+    }
+}
+
+impl fmt::Debug for LocalGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalGuard").finish_non_exhaustive()
+    }
+}
+
+/// Unique owner for a value which never leaves the current thread.
+///
+/// Unlike [Own](crate::Own), the value does not need to be [Send] or [Sync],
+/// so it can hold things like [Rc](std::rc::Rc) or [RefCell]. The generation
+/// counter is only accessed by the current thread, and references are accessed with a
+/// [LocalGuard] instead of pinning, so [LocalRef::get] is a plain compare.
+///
+/// ```
+///# use weakref::{LocalOwn, local_pin};
+///# use std::cell::RefCell;
+/// let data = LocalOwn::new_box(RefCell::new(vec![1, 2]));
+/// let weak = data.refer();
+/// weak.get(&local_pin()).unwrap().borrow_mut().push(3);
+/// assert_eq!(*data.borrow(), [1, 2, 3]);
+///
+/// drop(data);
+/// assert!(weak.get(&local_pin()).is_none());
+/// ```
+pub struct LocalOwn<P: IsPtr + 'static> {
+    weak: LocalRef<P::T>,
+}
+
+impl<P: IsPtr + 'static> LocalOwn<P> {
+    /// Wrap the given pointer so that it can inform local references when dropped.
+    pub fn new(ptr: P) -> Self {
+        let current_gen = new_local_counter();
+        LocalOwn {
+            weak: LocalRef {
+                current_gen,
+                expected_gen: current_gen.load(Ordering::Relaxed),
+                pointer: Some(P::into_raw_ptr(ptr)),
+            },
+        }
+    }
+
+    /// Provides the weak pointer.
+    pub fn refer(&self) -> LocalRef<P::T> {
+        self.weak
+    }
+}
+
+impl<T: 'static> LocalOwn<Box<T>> {
+    /// Allocates a box and wraps it with [LocalOwn::new].
+    pub fn new_box(value: T) -> Self {
+        Self::new(Box::new(value))
+    }
+}
+
+impl<P: IsPtr + 'static> Drop for LocalOwn<P> {
+    fn drop(&mut self) {
+        let weak = self.weak;
+        weak.current_gen
+            .store(weak.expected_gen + 1, Ordering::Relaxed);
+        // SAFETY: Pointer was returned by into_raw_ptr in LocalOwn::new
+        let ptr = unsafe { P::from_raw_ptr(weak.pointer.unwrap()) };
+
+        let reusable = can_reuse_local(weak.expected_gen + 1);
+        if !reusable {
+            crate::stats::counter_retired();
+        }
+        let ptr = LOCAL
+            .try_with(|local| {
+                if reusable {
+                    local.recycler.borrow_mut().push(weak.current_gen);
+                }
+                if local.guards.get() == 0 {
+                    return Some(ptr);
+                }
+                // A guard may still be borrowing the value.
+                local.pending.borrow_mut().push(Box::new(ptr));
+                None
+            })
+            .unwrap_or_else(|_| {
+                if reusable {
+                    EXITED_COUNTERS.push(weak.current_gen);
+                }
+                // Without thread-locals there can be no guards either.
+                None
+            });
+        drop(ptr);
+    }
+}
+
+impl<P: IsPtr + 'static> Deref for LocalOwn<P> {
+    type Target = P::T;
+
+    fn deref(&self) -> &P::T {
+        // SAFETY: Owner is alive, so pointer is valid
+        unsafe { self.weak.pointer.unwrap().as_ref() }
+    }
+}
+
+impl<P: IsPtr + 'static> fmt::Debug for LocalOwn<P>
+where
+    P::T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LocalOwn({:?})", &**self)
+    }
+}
+
+/// Weak reference for a value owned by a [LocalOwn] on the same thread.
+pub struct LocalRef<T: ?Sized> {
+    current_gen: LocalCounter,
+    expected_gen: usize,
+    pointer: Option<NonNull<T>>,
+}
+
+impl<T: ?Sized> Clone for LocalRef<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for LocalRef<T> {}
+
+impl<T: ?Sized> LocalRef<T> {
+    /// Check if the owner has been dropped. If it is alive, return the reference.
+    ///
+    /// The returned reference only borrows from the [LocalGuard], like [Ref::get](crate::Ref::get).
+    pub fn get(self, _guard: &LocalGuard) -> Option<&T> {
+        if self.current_gen.load(Ordering::Relaxed) == self.expected_gen {
+            // SAFETY: The owner is alive, and its value will not be dropped
+            // until every guard is gone
+            Some(unsafe { self.pointer?.as_ref() })
+        } else {
+            None
+        }
+    }
+
+    /// [Pin](local_pin) the current thread and check if the owner has been dropped. If it is alive, call `func` and return the output.
+    pub fn inspect<O>(self, func: impl FnOnce(&T) -> O) -> Option<O> {
+        self.get(&local_pin()).map(func)
+    }
+
+    /// Produces a new weak reference tied to self, which points to something reachable through the original pointer.
+    pub fn map<R: ?Sized>(self, func: impl FnOnce(&T) -> &R) -> LocalRef<R> {
+        LocalRef {
+            current_gen: self.current_gen,
+            expected_gen: self.expected_gen,
+            pointer: self
+                .get(&local_pin())
+                .map(|value| NonNull::from_ref(func(value))),
+        }
+    }
+
+    /// Checks whether the owner has been dropped.
+    pub fn is_alive(&self) -> bool {
+        self.current_gen.load(Ordering::Relaxed) == self.expected_gen && self.pointer.is_some()
+    }
+}
+
+impl<T: fmt::Debug + ?Sized> fmt::Debug for LocalRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.get(&local_pin()) {
+            Some(live) => write!(f, "LocalRef::Live({live:?})"),
+            None => f.debug_tuple("LocalRef::Dead").finish_non_exhaustive(),
+        }
+    }
+}
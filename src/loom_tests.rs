@@ -100,6 +100,47 @@ pub fn concurrent_recycler_stress() {
     });
 }
 
+#[test]
+pub fn concurrent_revoke_get() {
+    loom::model(|| {
+        crate::guts::empty_recycler();
+        let mut o = Own::new_box(42);
+        let r = o.refer();
+        let reader = loom::thread::spawn(move || {
+            let g = pin();
+            assert!(matches!(r.get(&g), Some(&42) | None));
+        });
+        let r2 = o.revoke();
+        let g = pin();
+        assert!(matches!(r.get(&g), None));
+        assert!(matches!(r2.get(&g), Some(&42)));
+        drop(g);
+        reader.join().unwrap();
+    });
+}
+
+#[test]
+pub fn concurrent_revoke_then_drop() {
+    loom::model(|| {
+        crate::guts::empty_recycler();
+        let mut o = Own::new_box(42);
+        let r1 = o.refer();
+        let r2 = o.revoke();
+        let r3 = o.refer();
+        loom::thread::spawn(move || {
+            let g = pin();
+            assert!(matches!(r1.get(&g), None));
+            assert!(matches!(r2.get(&g), Some(&42) | None));
+        });
+        loom::thread::spawn(move || {
+            let r4 = o.revoke();
+            assert!(matches!(r3.get(&pin()), None));
+            assert!(matches!(r4.get(&pin()), Some(&42)));
+            drop(o);
+        });
+    });
+}
+
 /*
 #[test]
 pub fn concurrent_replace_with_bad() {
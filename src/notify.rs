@@ -0,0 +1,389 @@
+use crate::guts::{GenerationCounter, IsPtr, Own, Ref};
+use crate::reclaim::Reclaim;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::task::Wake;
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+/// The number of slots in [WAITING]. Neighbouring counters never share a slot.
+const WAITING_SLOTS: usize = 4096;
+
+/// The number of hooks registered and threads sleeping in [Ref::wait_dead]
+/// for the counters in each slot, so that killing an owner can skip the side
+/// table and the futex wake unless its own counter (or a distant one sharing
+/// the slot) is being waited on.
+static WAITING: [AtomicUsize; WAITING_SLOTS] = [const { AtomicUsize::new(0) }; WAITING_SLOTS];
+
+/// Hooks for every generation counter, keyed by address.
+static HOOKS: LazyLock<Mutex<HashMap<usize, Vec<Hook>>>> = LazyLock::new(Default::default);
+
+static NEXT_WAITER_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Something to run when a generation counter advances.
+enum Hook {
+    /// Registered by [UntilDead].
+    Wake { id: u64, waker: Waker },
+    /// Registered by [Own::on_kill] for a specific generation.
+    OnKill {
+        generation: usize,
+        callback: Box<dyn FnOnce() + Send>,
+    },
+}
+
+fn key(counter: GenerationCounter) -> usize {
+    counter as *const _ as usize
+}
+
+fn waiting(counter: GenerationCounter) -> &'static AtomicUsize {
+    &WAITING[key(counter) / size_of::<AtomicUsize>() % WAITING_SLOTS]
+}
+
+/// Checks if the owner of a reference expecting `expected_gen` is dead. The
+/// counter is one generation behind while [Own::new_cyclic] is still building
+/// the value, which is not alive yet but not dead either.
+fn is_dead(current_gen: usize, expected_gen: usize) -> bool {
+    current_gen != expected_gen && current_gen.checked_add(1) != Some(expected_gen)
+}
+
+/// Wakes everything waiting on the counter, and runs the kill callbacks of the
+/// previous generation. Must be called after every successful
+/// [advance_generation](crate::guts::advance_generation).
+///
+/// Only costs a single load when nobody is waiting on the counter. Waiters
+/// increment its [WAITING] slot before checking the generation, and we load it
+/// after incrementing the generation, so at least one side is guaranteed to
+/// see the other.
+pub(crate) fn generation_advanced(counter: GenerationCounter, previous_gen: usize) {
+    for hook in take_hooks(counter) {
+        match hook {
+            Hook::Wake { waker, .. } => waker.wake(),
+            Hook::OnKill {
+                generation,
+                callback,
+            } => {
+                if generation == previous_gen {
+                    callback()
+                }
+            }
+        }
+    }
+}
+
+/// Like [generation_advanced], but the owner is still alive with a new
+/// counter and generation, so kill callbacks are moved instead of run.
+pub(crate) fn generation_revoked(
+    counter: GenerationCounter,
+    previous_gen: usize,
+    new_counter: GenerationCounter,
+    new_gen: usize,
+) {
+    for hook in take_hooks(counter) {
+        match hook {
+            Hook::Wake { waker, .. } => waker.wake(),
+            Hook::OnKill {
+                generation,
+                callback,
+            } => {
+                if generation == previous_gen {
+                    let hook = Hook::OnKill {
+                        generation: new_gen,
+                        callback,
+                    };
+                    register(new_counter, hook);
+                }
+            }
+        }
+    }
+}
+
+/// Removes every hook for the counter. They should only be run once the
+/// lock is released, in case they kill another owner.
+fn take_hooks(counter: GenerationCounter) -> Vec<Hook> {
+    if waiting(counter).load(Ordering::SeqCst) == 0 {
+        return Vec::new();
+    }
+    #[cfg(all(target_os = "linux", not(loom)))]
+    futex::wake_all(counter);
+
+    let hooks = HOOKS.lock().unwrap().remove(&key(counter));
+    let hooks = hooks.unwrap_or_default();
+    waiting(counter).fetch_sub(hooks.len(), Ordering::SeqCst);
+    hooks
+}
+
+fn register(counter: GenerationCounter, hook: Hook) {
+    let mut hooks = HOOKS.lock().unwrap();
+    waiting(counter).fetch_add(1, Ordering::SeqCst);
+    hooks.entry(key(counter)).or_default().push(hook);
+}
+
+fn unregister(counter: GenerationCounter, id: u64) {
+    let mut hooks = HOOKS.lock().unwrap();
+    if let Some(list) = hooks.get_mut(&key(counter)) {
+        if let Some(index) = list
+            .iter()
+            .position(|h| matches!(h, Hook::Wake { id: other, .. } if *other == id))
+        {
+            list.swap_remove(index);
+            waiting(counter).fetch_sub(1, Ordering::SeqCst);
+        }
+        if list.is_empty() {
+            hooks.remove(&key(counter));
+        }
+    }
+}
+
+impl<T: ?Sized, G> Ref<T, G> {
+    /// Returns a future which resolves once the owner has been dropped.
+    ///
+    /// The future is woken directly when the owner is killed, without any
+    /// polling, and works with any executor. For the reference given to
+    /// [Own::new_cyclic], it keeps waiting while the value is being built.
+    /// ```
+    ///# use weakref::Ref;
+    /// async fn cleanup(weak: Ref<String>) {
+    ///     weak.until_dead().await;
+    ///     println!("the owner is gone");
+    /// }
+    /// ```
+    pub fn until_dead(&self) -> UntilDead {
+        UntilDead {
+            current_gen: self.current_gen,
+            expected_gen: self.expected_gen,
+            id: None,
+        }
+    }
+
+    /// Blocks the current thread until the owner has been dropped, or the timeout elapses.
+    ///
+    /// Returns true if the owner is dead. On Linux the thread sleeps on a
+    /// futex of the generation counter itself, otherwise it parks until woken
+    /// by the same mechanism as [Ref::until_dead].
+    /// ```
+    ///# use weakref::Own;
+    ///# use std::time::Duration;
+    /// let data = Own::new_box(42);
+    /// let weak = data.refer();
+    /// assert!(!weak.wait_dead(Some(Duration::from_millis(1))));
+    ///
+    /// std::thread::spawn(move || drop(data));
+    /// assert!(weak.wait_dead(None));
+    /// ```
+    pub fn wait_dead(&self, timeout: Option<Duration>) -> bool {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        #[cfg(all(target_os = "linux", not(loom)))]
+        {
+            futex::wait_dead(self.current_gen, self.expected_gen, deadline)
+        }
+        #[cfg(not(all(target_os = "linux", not(loom))))]
+        {
+            park_until_dead(self.until_dead(), deadline)
+        }
+    }
+}
+
+#[cfg_attr(all(target_os = "linux", not(loom)), allow(unused))]
+fn park_until_dead(mut future: UntilDead, deadline: Option<Instant>) -> bool {
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if Pin::new(&mut future).poll(&mut cx).is_ready() {
+            return true;
+        }
+        match deadline {
+            None => thread::park(),
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return false;
+                }
+                thread::park_timeout(deadline - now);
+            }
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", not(loom)))]
+mod futex {
+    use super::waiting;
+    use crate::guts::GenerationCounter;
+    use core::sync::atomic::Ordering;
+    use std::time::Instant;
+
+    /// The futex syscall only works on 32 bits, so we use the least significant
+    /// half of the counter. That half changes on every increment.
+    fn futex_word(counter: GenerationCounter) -> *const u32 {
+        let ptr = counter as *const _ as *const u32;
+        if cfg!(target_endian = "big") && size_of::<usize>() == 8 {
+            ptr.wrapping_add(1)
+        } else {
+            ptr
+        }
+    }
+
+    pub(super) fn wait_dead(
+        counter: GenerationCounter,
+        expected_gen: usize,
+        deadline: Option<Instant>,
+    ) -> bool {
+        // Increment the waiting slot before checking the generation, pairing
+        // with the load in `generation_advanced`. The futex itself checks the
+        // value atomically, so the wake cannot be missed.
+        waiting(counter).fetch_add(1, Ordering::SeqCst);
+        let dead = loop {
+            let current_gen = counter.load(Ordering::SeqCst);
+            if super::is_dead(current_gen, expected_gen) {
+                break true;
+            }
+            let timeout = match deadline {
+                None => None,
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) if !remaining.is_zero() => Some(libc::timespec {
+                        tv_sec: remaining.as_secs() as libc::time_t,
+                        tv_nsec: remaining.subsec_nanos() as libc::c_long,
+                    }),
+                    _ => break false,
+                },
+            };
+            // SAFETY: The counter is never freed, and the timeout outlives the call
+            unsafe {
+                libc::syscall(
+                    libc::SYS_futex,
+                    futex_word(counter),
+                    libc::FUTEX_WAIT | libc::FUTEX_PRIVATE_FLAG,
+                    current_gen as u32,
+                    timeout
+                        .as_ref()
+                        .map_or(core::ptr::null(), |t| t as *const libc::timespec),
+                );
+            }
+        };
+        waiting(counter).fetch_sub(1, Ordering::SeqCst);
+        dead
+    }
+
+    pub(super) fn wake_all(counter: GenerationCounter) {
+        // SAFETY: The counter is never freed
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                futex_word(counter),
+                libc::FUTEX_WAKE | libc::FUTEX_PRIVATE_FLAG,
+                i32::MAX,
+            );
+        }
+    }
+}
+
+impl<P: IsPtr + Send + 'static, R: Reclaim> Own<P, R> {
+    /// Registers a callback to run when this owner is killed.
+    ///
+    /// Callbacks run on the thread which drops the owner (or passes it to
+    /// [Own::new_from]), in the order they were registered. By then the
+    /// generation has already been incremented, so every [Ref::get] fails.
+    /// But the destructor has not run yet, and will only run once no thread
+    /// can still be reading the value.
+    ///
+    /// Owners without callbacks pay nothing for this beyond a single atomic
+    /// load when dropped, since callbacks are only looked up for counters which
+    /// have some. (The waiter table is shared between distant counters, so
+    /// rarely an unrelated owner will also check the side table.)
+    /// ```
+    ///# use weakref::Own;
+    ///# use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+    /// let data = Own::new_box(42);
+    /// let weak = data.refer();
+    /// let killed = Arc::new(AtomicBool::new(false));
+    /// let flag = killed.clone();
+    /// data.on_kill(move || flag.store(true, Ordering::Relaxed));
+    ///
+    /// drop(data);
+    /// assert!(killed.load(Ordering::Relaxed));
+    /// ```
+    pub fn on_kill(&self, callback: impl FnOnce() + Send + 'static) {
+        let hook = Hook::OnKill {
+            generation: self._weak.expected_gen,
+            callback: Box::new(callback),
+        };
+        register(self._weak.current_gen, hook);
+    }
+}
+
+/// Future returned by [Ref::until_dead].
+#[must_use = "futures do nothing unless polled"]
+pub struct UntilDead {
+    current_gen: GenerationCounter,
+    expected_gen: usize,
+    /// Set while a waker is registered in the side table.
+    id: Option<u64>,
+}
+
+impl UntilDead {
+    fn is_dead(&self) -> bool {
+        is_dead(self.current_gen.load(Ordering::SeqCst), self.expected_gen)
+    }
+}
+
+impl Future for UntilDead {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.is_dead() {
+            if let Some(id) = self.id.take() {
+                unregister(self.current_gen, id);
+            }
+            return Poll::Ready(());
+        }
+
+        let mut hooks = HOOKS.lock().unwrap();
+        let existing = self.id.and_then(|id| {
+            hooks
+                .get_mut(&key(self.current_gen))?
+                .iter_mut()
+                .find_map(|hook| match hook {
+                    Hook::Wake { id: other, waker } if *other == id => Some(waker),
+                    _ => None,
+                })
+        });
+        match existing {
+            Some(waker) => waker.clone_from(cx.waker()),
+            None => {
+                drop(hooks);
+                let id = NEXT_WAITER_ID.fetch_add(1, Ordering::Relaxed);
+                let waker = cx.waker().clone();
+                register(self.current_gen, Hook::Wake { id, waker });
+                self.id = Some(id);
+            }
+        }
+
+        // The owner may have been killed before we were registered.
+        if self.is_dead() {
+            if let Some(id) = self.id.take() {
+                unregister(self.current_gen, id);
+            }
+            return Poll::Ready(());
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for UntilDead {
+    fn drop(&mut self) {
+        if let Some(id) = self.id.take() {
+            unregister(self.current_gen, id);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use crate::guts::{IsPtr, Own, Ref};
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicPtr, Ordering};
+use crossbeam_epoch::{Guard, pin};
+use std::fmt;
+
+/// Holds the latest published pointer, and drops it along with the owner.
+struct Published<P: IsPtr>
+where
+    P::T: Sized,
+{
+    current: AtomicPtr<P::T>,
+    _ptr: PhantomData<P>,
+}
+
+// SAFETY: The published value is shared across threads (requiring Sync) and
+// dropped on any thread (requiring Send).
+unsafe impl<P: IsPtr + Send> Send for Published<P> where P::T: Sized + Sync {}
+unsafe impl<P: IsPtr + Send> Sync for Published<P> where P::T: Sized + Sync {}
+
+impl<P: IsPtr> Drop for Published<P>
+where
+    P::T: Sized,
+{
+    fn drop(&mut self) {
+        let current = NonNull::new(*self.current.get_mut()).unwrap();
+        // SAFETY: Pointer was returned by into_raw_ptr in publish or new
+        drop(unsafe { P::from_raw_ptr(current) });
+    }
+}
+
+/// Unique owner for a value which can be replaced, without killing references.
+///
+/// Unlike [Own::new_from], a [RcuRef] follows the latest value passed to
+/// [RcuOwn::publish]. The old value is dropped once no thread can still be
+/// reading it. This is useful for config objects or lookup tables which get
+/// swapped out wholesale.
+///
+/// ```
+///# use weakref::{RcuOwn, pin};
+/// let mut config = RcuOwn::new(Box::new("v1"));
+/// let weak = config.refer();
+/// assert_eq!(weak.get(&pin()), Some(&"v1"));
+///
+/// config.publish(Box::new("v2"));
+/// assert_eq!(weak.get(&pin()), Some(&"v2"));
+///
+/// drop(config);
+/// assert_eq!(weak.get(&pin()), None);
+/// ```
+pub struct RcuOwn<P: IsPtr + Send + 'static>
+where
+    P::T: Sized + Sync,
+{
+    own: Own<Box<Published<P>>>,
+    weak: RcuRef<P::T>,
+}
+
+impl<P: IsPtr + Send + 'static> RcuOwn<P>
+where
+    P::T: Sized + Sync,
+{
+    /// Wrap the given pointer so that it can be replaced later.
+    pub fn new(ptr: P) -> Self {
+        let own = Own::new_box(Published {
+            current: AtomicPtr::new(P::into_raw_ptr(ptr).as_ptr()),
+            _ptr: PhantomData::<P>,
+        });
+        let weak = RcuRef {
+            cell: own.refer().map_with(|p| &p.current, &pin()),
+            _value: PhantomData,
+        };
+        RcuOwn { own, weak }
+    }
+
+    /// Provides the weak pointer, which follows every published value.
+    pub fn refer(&self) -> RcuRef<P::T> {
+        self.weak
+    }
+
+    /// Replaces the value seen by all references. The old value is dropped
+    /// once no thread can still be reading it.
+    pub fn publish(&mut self, ptr: P) {
+        let next = P::into_raw_ptr(ptr).as_ptr();
+        let guard = pin();
+        let prev = self.own.current.swap(next, Ordering::AcqRel);
+        let prev = NonNull::new(prev).unwrap();
+        // SAFETY: Pointer was returned by into_raw_ptr, and is no longer reachable
+        let prev = unsafe { P::from_raw_ptr(prev) };
+        guard.defer(move || drop(prev));
+    }
+}
+
+impl<P: IsPtr + Send + 'static> Deref for RcuOwn<P>
+where
+    P::T: Sized + Sync,
+{
+    type Target = P::T;
+
+    fn deref(&self) -> &P::T {
+        // SAFETY: Only `publish` replaces the value, which borrows self mutably
+        unsafe { &*self.own.current.load(Ordering::Acquire) }
+    }
+}
+
+impl<P: IsPtr + Send + 'static> fmt::Debug for RcuOwn<P>
+where
+    P::T: Sized + Sync + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RcuOwn({:?})", &**self)
+    }
+}
+
+/// Weak reference to the latest value of an [RcuOwn].
+///
+/// Access costs one more pointer load than [Ref::get].
+pub struct RcuRef<T> {
+    cell: Ref<AtomicPtr<T>>,
+    _value: PhantomData<Ref<T>>,
+}
+
+impl<T> Clone for RcuRef<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for RcuRef<T> {}
+
+impl<T> RcuRef<T> {
+    /// Check if the owner has been dropped. If it is alive, return the latest published value.
+    ///
+    /// Like [Ref::get], the returned reference stays valid until the thread is
+    /// unpinned, even if a new value is published in the meantime.
+    pub fn get(self, guard: &Guard) -> Option<&T> {
+        let current = self.cell.get(guard)?.load(Ordering::Acquire);
+        // SAFETY: Replaced values are only dropped once this thread is unpinned
+        Some(unsafe { &*current })
+    }
+
+    /// [Pin](pin) the current thread and check if the owner has been dropped. If it is alive, call `func` and return the output.
+    pub fn inspect<O>(self, func: impl FnOnce(&T) -> O) -> Option<O> {
+        self.get(&pin()).map(func)
+    }
+
+    /// Checks whether the owner has been dropped.
+    ///
+    /// Be aware there are no ordering guarentees, as with [Ref::is_alive].
+    pub fn is_alive(&self) -> bool {
+        self.cell.is_alive()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RcuRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.get(&pin()) {
+            Some(live) => write!(f, "RcuRef::Live({live:?})"),
+            None => f.debug_tuple("RcuRef::Dead").finish_non_exhaustive(),
+        }
+    }
+}
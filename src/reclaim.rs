@@ -0,0 +1,260 @@
+//! Backends which decide when the value of a killed [Own](crate::Own) can
+//! finally be dropped.
+//!
+//! By default every owner uses [Epoch], which defers to the global
+//! crossbeam-epoch collector. Any pinned [Guard] delays every destructor
+//! in the process, so a single long-held guard can stall reclamation for all
+//! owners. The [Hazard] backend instead tracks exactly which owners each
+//! [HazardGuard] has read from, so a long-held guard only delays those.
+//!
+//! The backend is picked per owner with [Own::new_in](crate::Own::new_in),
+//! and decides the guard type of its references. A [Domain](crate::Domain)
+//! is also a backend, which keeps the usual guard type.
+//!
+//! ```
+//!# use weakref::Own;
+//!# use weakref::reclaim::{Hazard, HazardGuard, IsGuard};
+//! let data = Own::new_in(Box::new(1), Hazard);
+//! let weak = data.refer();
+//!
+//! let guard = HazardGuard::pin();
+//! let value = weak.get(&guard).unwrap();
+//! drop(data);
+//! // The value is only dropped once the guard is gone.
+//! assert_eq!(*value, 1);
+//! ```
+
+use crate::guts::{GenerationCounter, RefId, new_generation_counter, recycle_generation_counter};
+use crate::stats;
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicUsize, Ordering, fence};
+use crossbeam_epoch::{Guard, pin};
+use crossbeam_queue::SegQueue;
+use std::collections::HashSet;
+use std::sync::{LazyLock, Mutex};
+
+/// A guard which keeps values alive while references are being read.
+///
+/// # Safety
+/// Once [IsGuard::protect] has returned for an owner, if the owner is found to
+/// still be alive then its value must not be dropped by the matching
+/// [Reclaim] backend until this guard is dropped.
+pub unsafe trait IsGuard {
+    /// Creates a guard for the current thread.
+    fn pin() -> Self;
+
+    /// Called by [Ref::get](crate::Ref::get) before checking the generation.
+    fn protect(&self, id: RefId);
+}
+
+/// Decides when the value of a killed owner can be dropped.
+///
+/// # Safety
+/// `retire` must never run `drop` while a [Reclaim::Guard] which protected
+/// the owner (and found it alive) still exists.
+pub unsafe trait Reclaim: Send + Sync + 'static {
+    /// The guard used to read references of owners with this backend.
+    type Guard: IsGuard;
+
+    /// Runs `drop` once no guard can still be reading the value of `id`,
+    /// which has already been killed.
+    fn retire(&self, id: RefId, drop: impl FnOnce() + Send + 'static);
+
+    /// Tries to run any destructors which have become safe.
+    fn flush(&self);
+
+    /// Provides a generation counter for a new owner.
+    #[doc(hidden)]
+    fn take_counter(&self) -> GenerationCounter {
+        new_generation_counter()
+    }
+
+    /// Accepts the counter of a killed owner, which can be killed at least once more.
+    #[doc(hidden)]
+    fn give_counter(&self, counter: GenerationCounter) {
+        recycle_generation_counter(counter)
+    }
+}
+
+// SAFETY: Guards protect every value which was reachable when pinned
+unsafe impl IsGuard for Guard {
+    fn pin() -> Self {
+        pin()
+    }
+
+    #[inline]
+    fn protect(&self, _id: RefId) {}
+}
+
+/// The default backend, using the global crossbeam-epoch collector.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Epoch;
+
+// SAFETY: Deferred functions only run once every thread pinned at the time is unpinned
+unsafe impl Reclaim for Epoch {
+    type Guard = Guard;
+
+    fn retire(&self, _id: RefId, drop: impl FnOnce() + Send + 'static) {
+        pin().defer(drop);
+    }
+
+    fn flush(&self) {
+        pin().flush();
+    }
+}
+
+/// Hazard pointer backend, where each guard only delays the values it read.
+///
+/// Every [HazardGuard] publishes the generation counters it has accessed.
+/// Killed values are kept in a global list, which is scanned every so
+/// often for values which are not protected by any guard. Accessing a
+/// reference costs an extra store and fence compared to [Epoch].
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Hazard;
+
+/// The number of owners a guard can protect before falling back to a lock.
+const HAZARD_SLOTS: usize = 8;
+
+/// The list is scanned when it grows past this size, or twice the number of
+/// values which were still protected after the last scan.
+const SCAN_THRESHOLD: usize = 64;
+
+static NEXT_SCAN: AtomicUsize = AtomicUsize::new(SCAN_THRESHOLD);
+
+/// The hazards published by a single guard. Records are leaked and reused.
+struct HazardRecord {
+    /// Counter addresses, or 0 if empty.
+    slots: [AtomicUsize; HAZARD_SLOTS],
+    overflow: Mutex<HashSet<usize>>,
+}
+
+type Retired = (usize, Box<dyn FnOnce() + Send>);
+
+/// Every record ever created, for scanning.
+static RECORDS: Mutex<Vec<&'static HazardRecord>> = Mutex::new(Vec::new());
+/// Records which are not used by any guard.
+static FREE_RECORDS: SegQueue<&'static HazardRecord> = SegQueue::new();
+static RETIRED: LazyLock<Mutex<Vec<Retired>>> = LazyLock::new(Default::default);
+
+/// Guard for references owned with the [Hazard] backend.
+pub struct HazardGuard {
+    record: &'static HazardRecord,
+    len: Cell<usize>,
+    _not_send: PhantomData<*const ()>,
+}
+
+// SAFETY: The counter is published with a fence before its generation is checked
+unsafe impl IsGuard for HazardGuard {
+    fn pin() -> Self {
+        let record = FREE_RECORDS.pop().unwrap_or_else(|| {
+            let record = Box::leak(Box::new(HazardRecord {
+                slots: Default::default(),
+                overflow: Mutex::new(HashSet::new()),
+            }));
+            RECORDS.lock().unwrap().push(record);
+            stats::hazard_record_leaked();
+            record
+        });
+        HazardGuard {
+            record,
+            len: Cell::new(0),
+            _not_send: PhantomData,
+        }
+    }
+
+    fn protect(&self, id: RefId) {
+        let len = self.len.get();
+        let slots = &self.record.slots[..len.min(HAZARD_SLOTS)];
+        if slots
+            .iter()
+            .any(|s| s.load(Ordering::Relaxed) == id.counter)
+        {
+            return;
+        }
+        match self.record.slots.get(len) {
+            Some(slot) => slot.store(id.counter, Ordering::Relaxed),
+            None => {
+                if !self.record.overflow.lock().unwrap().insert(id.counter) {
+                    return;
+                }
+            }
+        }
+        self.len.set(len + 1);
+        // Pairs with the fence in `scan`, so either the scan sees this hazard
+        // or the caller sees the incremented generation.
+        fence(Ordering::SeqCst);
+    }
+}
+
+impl HazardGuard {
+    /// The number of distinct owners this guard has protected.
+    #[cfg(test)]
+    pub(crate) fn protected(&self) -> usize {
+        self.len.get()
+    }
+}
+
+impl Drop for HazardGuard {
+    fn drop(&mut self) {
+        let len = self.len.get();
+        for slot in &self.record.slots[..len.min(HAZARD_SLOTS)] {
+            slot.store(0, Ordering::Release);
+        }
+        if len > HAZARD_SLOTS {
+            self.record.overflow.lock().unwrap().clear();
+        }
+        FREE_RECORDS.push(self.record);
+    }
+}
+
+/// Runs every retired destructor whose counter is not protected by a guard.
+fn scan() {
+    fence(Ordering::SeqCst);
+    let mut hazards = HashSet::new();
+    for record in RECORDS.lock().unwrap().iter() {
+        for slot in &record.slots {
+            match slot.load(Ordering::Acquire) {
+                0 => (),
+                counter => {
+                    hazards.insert(counter);
+                }
+            }
+        }
+        hazards.extend(record.overflow.lock().unwrap().iter().copied());
+    }
+
+    let ready: Vec<Retired> = {
+        let mut retired = RETIRED.lock().unwrap();
+        let ready = retired
+            .extract_if(.., |(counter, _)| !hazards.contains(counter))
+            .collect();
+        // Protected values would be rescanned on every retire otherwise.
+        NEXT_SCAN.store(SCAN_THRESHOLD.max(retired.len() * 2), Ordering::Relaxed);
+        ready
+    };
+    // Destructors may retire more values, so the lock must be released first.
+    for (_, drop) in ready {
+        drop();
+    }
+}
+
+// SAFETY: Values are only dropped by `scan`, which checks every published hazard
+unsafe impl Reclaim for Hazard {
+    type Guard = HazardGuard;
+
+    fn retire(&self, id: RefId, drop: impl FnOnce() + Send + 'static) {
+        let len = {
+            let mut retired = RETIRED.lock().unwrap();
+            retired.push((id.counter, Box::new(drop)));
+            retired.len()
+        };
+        if len >= NEXT_SCAN.load(Ordering::Relaxed) {
+            scan();
+        }
+    }
+
+    fn flush(&self) {
+        scan();
+    }
+}
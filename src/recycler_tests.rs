@@ -1,5 +1,5 @@
 use crate::Own;
-use crate::guts::{empty_recycler, local_recycler_len, global_recycler_len};
+use crate::guts::{empty_recycler, local_recycler_len, global_recycler_len, global_leftovers_len};
 
 #[test]
 fn recycler_starts_empty() {
@@ -72,4 +72,151 @@ fn recycler_pulls_from_global_when_local_empty() {
     drop(o);
     
     assert!(local_recycler_len() > 0);
-}
\ No newline at end of file
+}
+#[test]
+fn stats_count_owns() {
+    let before = crate::stats();
+
+    let objects: Vec<_> = (0..10).map(Own::new_box).collect();
+    let created = crate::stats();
+    assert!(created.owns_created - before.owns_created >= 10);
+    assert!(created.blocks_leaked >= 1);
+    assert!(created.counters_leaked >= created.blocks_leaked);
+
+    drop(objects);
+    let killed = crate::stats();
+    assert!(killed.owns_killed - before.owns_killed >= 10);
+}
+
+#[test]
+fn stats_keep_exited_threads() {
+    let before = crate::stats();
+    std::thread::spawn(|| {
+        for i in 0..5 {
+            drop(Own::new_box(i));
+        }
+    })
+    .join()
+    .unwrap();
+    let after = crate::stats();
+    assert!(after.owns_created - before.owns_created >= 5);
+    assert!(after.owns_killed - before.owns_killed >= 5);
+}
+
+#[test]
+fn recycler_flushes_when_thread_exits() {
+    let cached = std::thread::spawn(|| {
+        let o = Own::new_box(42);
+        drop(o);
+        local_recycler_len()
+    })
+    .join()
+    .unwrap();
+
+    assert!(cached > 0);
+    let global = global_recycler_len() * crate::guts::BLOCK_SIZE + global_leftovers_len();
+    assert!(global >= cached);
+}
+
+#[test]
+fn recycler_flush_thread_cache() {
+    std::thread::spawn(|| {
+        let objects: Vec<_> = (0..10).map(Own::new_box).collect();
+        drop(objects);
+        assert!(local_recycler_len() > 0);
+
+        crate::flush_thread_cache();
+        assert_eq!(local_recycler_len(), 0);
+        assert!(global_recycler_len() + global_leftovers_len() > 0);
+
+        let o = Own::new_box(42);
+        drop(o);
+        assert!(local_recycler_len() > 0);
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn recycler_reserve_without_allocation() {
+    std::thread::spawn(|| {
+        crate::reserve(600);
+        assert!(local_recycler_len() >= 600);
+
+        crate::forbid_counter_allocation(true);
+        let objects: Vec<_> = (0..600).map(Own::new_box).collect();
+        drop(objects);
+        assert!(local_recycler_len() >= 600);
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn padded_domain_forbidden_allocation_panics() {
+    let result = std::thread::spawn(|| {
+        let domain = crate::Domain::padded();
+        domain.reserve(1);
+        crate::forbid_counter_allocation(true);
+        let reserved: Vec<_> = (0..domain.pooled_counters())
+            .map(|i| domain.own(Box::new(i)))
+            .collect();
+        drop(reserved);
+        // Killed counters return to the pool, but a new owner needs one more.
+        let _owners: Vec<_> = (0..=domain.pooled_counters())
+            .map(|i| domain.own(Box::new(i)))
+            .collect();
+    })
+    .join();
+    let message = *result.unwrap_err().downcast::<&str>().unwrap();
+    assert!(message.contains("Domain::reserve"));
+}
+
+#[test]
+fn recycler_forbidden_allocation_panics() {
+    let result = std::thread::spawn(|| {
+        crate::forbid_counter_allocation(true);
+        let _o = Own::new_box(1);
+    })
+    .join();
+    assert!(result.is_err());
+}
+
+#[test]
+fn stats_count_local_blocks_and_hazard_records() {
+    use crate::LocalOwn;
+    use crate::local::LOCAL_BLOCKS_LEAKED;
+    use crate::reclaim::{HazardGuard, IsGuard};
+    use core::sync::atomic::Ordering;
+
+    let before = crate::stats();
+    let local_before = LOCAL_BLOCKS_LEAKED.load(Ordering::Relaxed);
+    for i in 0..4 {
+        std::thread::spawn(move || drop(LocalOwn::new_box(i)))
+            .join()
+            .unwrap();
+    }
+    let local_leaked = LOCAL_BLOCKS_LEAKED.load(Ordering::Relaxed) - local_before;
+    let after = crate::stats();
+    assert!(after.blocks_leaked - before.blocks_leaked >= local_leaked);
+
+    let guard = HazardGuard::pin();
+    assert!(crate::stats().hazard_records_leaked >= 1);
+    drop(guard);
+}
+
+#[test]
+fn local_counters_reused_after_thread_exit() {
+    use crate::LocalOwn;
+    use crate::local::LOCAL_BLOCKS_LEAKED;
+    use core::sync::atomic::Ordering;
+
+    let before = LOCAL_BLOCKS_LEAKED.load(Ordering::Relaxed);
+    for i in 0..32 {
+        std::thread::spawn(move || drop(LocalOwn::new_box(i)))
+            .join()
+            .unwrap();
+    }
+    // Other tests may leak a few blocks of their own meanwhile.
+    assert!(LOCAL_BLOCKS_LEAKED.load(Ordering::Relaxed) - before < 8);
+}
@@ -0,0 +1,112 @@
+use crate::guts::{
+    GenerationCounter, Ref, advance_generation, can_reuse, new_generation_counter,
+    recycle_generation_counter,
+};
+use crate::stats;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+use crossbeam_epoch::pin;
+
+#[cfg(not(loom))]
+use core::sync::atomic::Ordering;
+#[cfg(loom)]
+use loom::sync::atomic::Ordering;
+
+/// Owns many values under a single generation counter.
+///
+/// Every [Ref] allocated in a region dies at once when the region is dropped.
+/// This only increments the counter a single time, and defers all the
+/// destructors together. Useful when a whole subsystem is torn down at once.
+///
+/// ```
+///# use weakref::{Region, pin};
+/// let mut level = Region::new();
+/// let name = level.alloc(String::from("dungeon"));
+/// let depth = level.alloc(3);
+/// assert_eq!(depth.get(&pin()), Some(&3));
+///
+/// drop(level);
+/// assert_eq!(name.get(&pin()), None);
+/// assert_eq!(depth.get(&pin()), None);
+/// ```
+pub struct Region {
+    current_gen: GenerationCounter,
+    expected_gen: usize,
+    values: Values,
+}
+
+/// Boxed values, which are all dropped together.
+#[derive(Default)]
+struct Values(Vec<NonNull<dyn Send>>);
+
+// SAFETY: Every value is Send, and values are only shared through [Ref]
+unsafe impl Send for Values {}
+unsafe impl Sync for Values {}
+
+impl Drop for Values {
+    fn drop(&mut self) {
+        for value in self.0.drain(..) {
+            // SAFETY: Pointer was returned by Box::into_raw in Region::alloc
+            drop(unsafe { Box::from_raw(value.as_ptr()) });
+        }
+    }
+}
+
+impl Region {
+    /// Creates an empty region, taking a generation counter from the global pool.
+    pub fn new() -> Self {
+        let current_gen = new_generation_counter();
+        let expected_gen = current_gen.load(Ordering::Acquire);
+        Region {
+            current_gen,
+            expected_gen,
+            values: Values::default(),
+        }
+    }
+
+    /// Moves the value into the region and provides the weak pointer.
+    pub fn alloc<T: Send + 'static>(&mut self, value: T) -> Ref<T> {
+        let pointer = NonNull::new(Box::into_raw(Box::new(value))).unwrap();
+        self.values.0.push(pointer);
+        Ref {
+            current_gen: self.current_gen,
+            expected_gen: self.expected_gen,
+            pointer: Some(pointer),
+            _guard: PhantomData,
+        }
+    }
+
+    /// The number of values allocated in the region.
+    pub fn len(&self) -> usize {
+        self.values.0.len()
+    }
+
+    /// Returns true if nothing has been allocated in the region.
+    pub fn is_empty(&self) -> bool {
+        self.values.0.is_empty()
+    }
+}
+
+impl Default for Region {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Region {
+    fn drop(&mut self) {
+        let guard = pin();
+        if !advance_generation(self.current_gen, self.expected_gen) {
+            panic!("Tried to drop a dead region");
+        }
+
+        let values = std::mem::take(&mut self.values);
+        guard.defer(move || drop(values));
+
+        if can_reuse(self.expected_gen + 1) {
+            recycle_generation_counter(self.current_gen);
+        } else {
+            stats::counter_retired();
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use crate::guts::{IsPtr, Own, recycle_generation_counter};
+use core::future::Future;
+use core::ops::DerefMut;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+use crossbeam_epoch::{is_pinned, pin};
+use std::sync::{Arc, Mutex};
+
+impl<P: IsPtr + Send + 'static> Own<P> {
+    /// Kills all references, then blocks until no thread can still be reading
+    /// the value before giving back the pointer. Similar to `synchronize_rcu`.
+    ///
+    /// # Panics
+    /// If the current thread is [pinned](pin), since it would wait on itself forever.
+    /// ```
+    ///# use weakref::{Own, pin};
+    /// let data = Own::new_box(42);
+    /// let weak = data.refer();
+    /// let inner: Box<i32> = data.into_inner();
+    /// assert_eq!(*inner, 42);
+    /// assert_eq!(weak.get(&pin()), None);
+    /// ```
+    pub fn into_inner(self) -> P {
+        // Check before killing, so a panic drops the owner as usual.
+        assert_not_pinned();
+        self.retire().wait()
+    }
+
+    /// Like dropping the owner, but the destructor runs before this returns
+    /// instead of at some unpredictable point later. See [Own::into_inner].
+    pub fn drop_now(self) {
+        drop(self.into_inner());
+    }
+
+    /// Kills all references without blocking, returning a handle which gives
+    /// back the pointer once no thread can still be reading the value.
+    pub fn retire(self) -> Retired<P> {
+        let guard = pin();
+        let (ptr, counter) = self.take();
+        let reclaimed = Arc::new(Reclaimed::default());
+        let flag = reclaimed.clone();
+        guard.defer(move || flag.set());
+        // Otherwise the flag could wait in this thread's bag forever, if the
+        // thread goes idle while another one waits.
+        guard.flush();
+        drop(guard);
+        if let Some(counter) = counter {
+            recycle_generation_counter(counter);
+        }
+        Retired {
+            ptr: Some(ptr),
+            reclaimed,
+        }
+    }
+}
+
+impl<P: IsPtr + DerefMut<Target = P::T> + Send + 'static> Own<P> {
+    /// Provides unique mutable access to the value, by revoking every
+    /// reference and blocking until no thread can still be reading it.
+    ///
+    /// Afterwards [Own::refer] provides fresh references, as with [Own::revoke].
+    /// The pointer must implement [DerefMut], which excludes shared pointers
+    /// like [Arc] and pinned values which are not [Unpin].
+    ///
+    /// # Panics
+    /// If the current thread is [pinned](pin), since it would wait on itself forever.
+    /// ```
+    ///# use weakref::{Own, pin};
+    /// let mut data = Own::new(vec![1, 2, 3]);
+    /// let old = data.refer();
+    /// data.make_mut()[0] = 4;
+    /// assert_eq!(old.get(&pin()), None);
+    /// assert_eq!(data.refer().get(&pin()), Some(&[4, 2, 3][..]));
+    /// ```
+    pub fn make_mut(&mut self) -> &mut P::T {
+        // Check before revoking, so a panic leaves every reference alive.
+        assert_not_pinned();
+        self.revoke();
+        synchronize();
+        // SAFETY: No reference can access the value anymore, and P allows unique access
+        unsafe { self._weak.pointer.unwrap().as_mut() }
+    }
+}
+
+/// Blocks until every thread which is currently pinned has been unpinned.
+fn synchronize() {
+    let reclaimed = Arc::new(Reclaimed::default());
+    let flag = reclaimed.clone();
+    pin().defer(move || flag.set());
+    wait_for(&reclaimed);
+}
+
+fn wait_for(reclaimed: &Reclaimed) {
+    assert_not_pinned();
+    while !reclaimed.is_set() {
+        pin().flush();
+        std::thread::yield_now();
+    }
+}
+
+fn assert_not_pinned() {
+    assert!(
+        !is_pinned(),
+        "Tried to wait for readers while the current thread is pinned"
+    );
+}
+
+/// Set by a deferred function once no thread can still be reading the value.
+#[derive(Default)]
+struct Reclaimed {
+    flag: AtomicBool,
+    /// The task awaiting the [Retired], if any.
+    waker: Mutex<Option<Waker>>,
+}
+
+impl Reclaimed {
+    fn is_set(&self) -> bool {
+        self.flag.load(Ordering::Acquire)
+    }
+
+    fn set(&self) {
+        self.flag.store(true, Ordering::Release);
+        if let Some(waker) = self.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+}
+
+/// An owner which has been killed, but whose value may still be read by other threads.
+///
+/// Created by [Own::retire]. The pointer is available once every thread which
+/// was pinned at the time has been unpinned. It can be polled with
+/// [Retired::try_take], blocked on with [Retired::wait], or awaited. If the
+/// handle is dropped, the value is dropped as usual once that is safe.
+///
+/// Progress depends on the epoch advancing, so waiting will pin and flush the
+/// current thread repeatedly. Awaiting only flushes when polled, and is then
+/// woken by whichever thread runs the deferred functions.
+#[must_use = "dropping a retired owner is equivalent to dropping the owner"]
+pub struct Retired<P: IsPtr + Send + 'static> {
+    ptr: Option<P>,
+    reclaimed: Arc<Reclaimed>,
+}
+
+// SAFETY: The value is never accessed until readers on other threads are gone
+unsafe impl<P: IsPtr + Send + 'static> Sync for Retired<P> {}
+
+impl<P: IsPtr + Send + 'static> Retired<P> {
+    /// Returns true once no thread can still be reading the value.
+    pub fn is_ready(&self) -> bool {
+        self.reclaimed.is_set()
+    }
+
+    /// Gives back the pointer if no thread can still be reading the value.
+    pub fn try_take(mut self) -> Result<P, Self> {
+        if self.is_ready() {
+            Ok(self.ptr.take().unwrap())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Blocks until no thread can still be reading the value, then gives back the pointer.
+    ///
+    /// # Panics
+    /// If the current thread is [pinned](pin), since it would wait on itself forever.
+    pub fn wait(mut self) -> P {
+        wait_for(&self.reclaimed);
+        self.ptr.take().unwrap()
+    }
+}
+
+impl<P: IsPtr + Send + 'static> Future for Retired<P> {
+    type Output = P;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<P> {
+        // The epoch must advance twice, which is enough when no other thread is pinned.
+        for _ in 0..3 {
+            if self.is_ready() {
+                break;
+            }
+            pin().flush();
+        }
+        if !self.is_ready() {
+            self.reclaimed
+                .waker
+                .lock()
+                .unwrap()
+                .replace(cx.waker().clone());
+        }
+        // Check again, in case the flag was set before the waker was stored.
+        if self.is_ready() {
+            Poll::Ready(self.ptr.take().expect("polled after completion"))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<P: IsPtr + Send + 'static> Unpin for Retired<P> {}
+
+impl<P: IsPtr + Send + 'static> Drop for Retired<P> {
+    fn drop(&mut self) {
+        if let Some(ptr) = self.ptr.take() {
+            pin().defer(move || drop(ptr));
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::guts::{IsPtr, Own, Ref, RefId};
+use core::ops::Deref;
+use std::fmt;
+use std::sync::Arc;
+
+/// Owner for a value which can be cloned, so that references only die once
+/// every clone has been dropped.
+///
+/// References are ordinary [Ref]s, sharing a generation counter with the
+/// inner [Own]. The owner count is kept separately, so the counter is only
+/// incremented (and the destructor deferred) when the last owner is dropped.
+///
+/// ```
+///# use weakref::{SharedOwn, pin};
+/// let a = SharedOwn::new(Box::new(42));
+/// let b = a.clone();
+/// let weak = a.refer();
+///
+/// drop(a);
+/// assert_eq!(weak.get(&pin()), Some(&42));
+/// drop(b);
+/// assert_eq!(weak.get(&pin()), None);
+/// ```
+pub struct SharedOwn<P: IsPtr + Send + 'static> {
+    own: Arc<Own<P>>,
+    /// A copy of the weak reference, so that [refer](crate::refer) works.
+    #[doc(hidden)]
+    pub _weak: Ref<P::T>,
+}
+
+impl<P: IsPtr + Send + 'static> SharedOwn<P> {
+    /// Wrap the given pointer so that it can have several owners.
+    pub fn new(ptr: P) -> Self {
+        Own::new(ptr).into()
+    }
+
+    /// Provides the weak pointer.
+    pub fn refer(&self) -> Ref<P::T> {
+        self._weak
+    }
+
+    /// Identifies this owner. Equal to [Ref::id] for all of its references.
+    pub fn id(&self) -> RefId {
+        self._weak.id()
+    }
+
+    /// The number of clones of this owner which are still alive, including this one.
+    pub fn owner_count(&self) -> usize {
+        Arc::strong_count(&self.own)
+    }
+
+    /// Gives back the unique owner if this is the last clone. References stay alive.
+    pub fn try_unwrap(self) -> Result<Own<P>, Self> {
+        let SharedOwn { own, _weak } = self;
+        Arc::try_unwrap(own).map_err(|own| SharedOwn { own, _weak })
+    }
+}
+
+impl<P: IsPtr + Send + 'static> From<Own<P>> for SharedOwn<P> {
+    fn from(own: Own<P>) -> Self {
+        SharedOwn {
+            _weak: own.refer(),
+            own: Arc::new(own),
+        }
+    }
+}
+
+impl<P: IsPtr + Send + 'static> Clone for SharedOwn<P> {
+    fn clone(&self) -> Self {
+        SharedOwn {
+            own: self.own.clone(),
+            _weak: self._weak,
+        }
+    }
+}
+
+impl<P: IsPtr + Send + 'static> Deref for SharedOwn<P> {
+    type Target = P::T;
+
+    fn deref(&self) -> &P::T {
+        &self.own
+    }
+}
+
+impl<P: IsPtr + Send + 'static> fmt::Debug for SharedOwn<P>
+where
+    P::T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SharedOwn({:?})", &**self)
+    }
+}
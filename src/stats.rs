@@ -0,0 +1,162 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// A snapshot of the generation counters and owners in the process, from [stats].
+///
+/// Values are gathered from every thread without any synchronization, so
+/// they may be slightly inconsistent with each other while other threads
+/// are busy.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Stats {
+    /// Blocks of counters which were leaked, and can never be freed.
+    pub blocks_leaked: usize,
+    /// Total counters which were leaked, including any in use and those of
+    /// [LocalOwn](crate::LocalOwn)s.
+    pub counters_leaked: usize,
+    /// Counters waiting for reuse in the pools of every thread.
+    pub local_pooled: usize,
+    /// Counters waiting for reuse in the global pool.
+    pub global_pooled: usize,
+    /// Counters which reached the last generation, and can never be reused.
+    pub counters_retired: usize,
+    /// Owners which were created, since the process started.
+    pub owns_created: usize,
+    /// Owners which were killed, since the process started.
+    pub owns_killed: usize,
+    /// Records which were leaked for [HazardGuard](crate::reclaim::HazardGuard)s,
+    /// one for each guard which was ever alive at the same time as the others.
+    pub hazard_records_leaked: usize,
+}
+
+impl Stats {
+    /// The number of owners which are still alive.
+    pub fn owns_alive(&self) -> usize {
+        self.owns_created.saturating_sub(self.owns_killed)
+    }
+}
+
+/// Reports how many generation counters exist, and where they are.
+///
+/// Tracking is always enabled, and only costs a few uncontended atomic
+/// stores per owner. Counters held by a [Domain](crate::Domain) are not
+/// included in the pools.
+/// ```
+/// let before = weakref::stats();
+/// let data = weakref::Own::new_box(42);
+/// let after = weakref::stats();
+/// assert_eq!(after.owns_created - before.owns_created, 1);
+/// assert!(after.counters_leaked >= after.local_pooled + after.global_pooled);
+///# drop(data);
+/// ```
+pub fn stats() -> Stats {
+    // Exiting threads fold their stats while holding the lock, so they are never missed.
+    let threads = THREADS.lock().unwrap();
+    let mut stats = Stats {
+        blocks_leaked: BLOCKS_LEAKED.load(Ordering::Relaxed),
+        counters_leaked: COUNTERS_LEAKED.load(Ordering::Relaxed),
+        global_pooled: crate::guts::global_pooled(),
+        counters_retired: COUNTERS_RETIRED.load(Ordering::Relaxed),
+        owns_created: EXITED.owns_created.load(Ordering::Relaxed),
+        owns_killed: EXITED.owns_killed.load(Ordering::Relaxed),
+        hazard_records_leaked: HAZARD_RECORDS_LEAKED.load(Ordering::Relaxed),
+        ..Stats::default()
+    };
+    for thread in threads.iter() {
+        stats.local_pooled += thread.local_pooled.load(Ordering::Relaxed);
+        stats.owns_created += thread.owns_created.load(Ordering::Relaxed);
+        stats.owns_killed += thread.owns_killed.load(Ordering::Relaxed);
+    }
+    stats
+}
+
+static BLOCKS_LEAKED: AtomicUsize = AtomicUsize::new(0);
+static COUNTERS_LEAKED: AtomicUsize = AtomicUsize::new(0);
+static COUNTERS_RETIRED: AtomicUsize = AtomicUsize::new(0);
+static HAZARD_RECORDS_LEAKED: AtomicUsize = AtomicUsize::new(0);
+
+/// The totals of every thread which has exited.
+static EXITED: ThreadStats = ThreadStats::new();
+
+/// Every thread which is currently tracking stats.
+static THREADS: Mutex<Vec<Arc<ThreadStats>>> = Mutex::new(Vec::new());
+
+/// Stats for a single thread. Only that thread writes to them, so updates
+/// don't need atomic read-modify-writes.
+struct ThreadStats {
+    local_pooled: AtomicUsize,
+    owns_created: AtomicUsize,
+    owns_killed: AtomicUsize,
+}
+
+impl ThreadStats {
+    const fn new() -> Self {
+        ThreadStats {
+            local_pooled: AtomicUsize::new(0),
+            owns_created: AtomicUsize::new(0),
+            owns_killed: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// Registers the stats of the current thread, and folds them into
+/// [EXITED] when the thread exits.
+struct LocalStats(Arc<ThreadStats>);
+
+impl LocalStats {
+    fn new() -> Self {
+        let stats = Arc::new(ThreadStats::new());
+        THREADS.lock().unwrap().push(stats.clone());
+        LocalStats(stats)
+    }
+}
+
+impl Drop for LocalStats {
+    fn drop(&mut self) {
+        let mut threads = THREADS.lock().unwrap();
+        threads.retain(|thread| !Arc::ptr_eq(thread, &self.0));
+        let created = self.0.owns_created.load(Ordering::Relaxed);
+        let killed = self.0.owns_killed.load(Ordering::Relaxed);
+        EXITED.owns_created.fetch_add(created, Ordering::Relaxed);
+        EXITED.owns_killed.fetch_add(killed, Ordering::Relaxed);
+    }
+}
+
+thread_local! {
+    static LOCAL_STATS: LocalStats = LocalStats::new();
+}
+
+fn increment(field: fn(&ThreadStats) -> &AtomicUsize) {
+    let local = LOCAL_STATS.try_with(|local| {
+        let counter = field(&local.0);
+        counter.store(counter.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+    });
+    // The thread is exiting and its stats were already folded.
+    if local.is_err() {
+        field(&EXITED).fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub(crate) fn own_created() {
+    increment(|stats| &stats.owns_created);
+}
+
+pub(crate) fn own_killed() {
+    increment(|stats| &stats.owns_killed);
+}
+
+pub(crate) fn set_local_pooled(len: usize) {
+    let _ = LOCAL_STATS.try_with(|local| local.0.local_pooled.store(len, Ordering::Relaxed));
+}
+
+pub(crate) fn block_leaked(counters: usize) {
+    BLOCKS_LEAKED.fetch_add(1, Ordering::Relaxed);
+    COUNTERS_LEAKED.fetch_add(counters, Ordering::Relaxed);
+}
+
+pub(crate) fn counter_retired() {
+    COUNTERS_RETIRED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn hazard_record_leaked() {
+    HAZARD_RECORDS_LEAKED.fetch_add(1, Ordering::Relaxed);
+}
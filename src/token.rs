@@ -0,0 +1,147 @@
+use crate::guts::{Ref, RefId};
+use crate::weak_key_map::PurgeLen;
+use core::hash::Hasher;
+use siphasher::sip::SipHasher24;
+use std::collections::HashMap;
+
+/// Converts references into opaque tokens which are safe to hand to untrusted clients.
+///
+/// A token is a `u128` made of a table-local id and a SipHash-2-4 MAC of
+/// that id, keyed with a secret from the OS random number generator which is
+/// chosen for each table. It contains no
+/// addresses or generations, so a client cannot use it to probe memory, and
+/// guessing a valid token is as hard as guessing the MAC. Tokens which were
+/// forged, issued by a different table, or whose referent has died all fail to
+/// resolve. Use [u128::to_be_bytes] to send a token as bytes.
+///
+/// ```
+///# use weakref::{Own, TokenTable, pin};
+/// let data = Own::new_box("secret");
+/// let mut tokens = TokenTable::new();
+/// let token = tokens.issue(data.refer());
+/// assert_eq!(tokens.resolve(token).unwrap().get(&pin()), Some(&"secret"));
+///
+/// assert!(tokens.resolve(token ^ 1).is_none());
+/// assert!(TokenTable::<&str>::new().resolve(token).is_none());
+/// drop(data);
+/// assert!(tokens.resolve(token).is_none());
+/// ```
+pub struct TokenTable<T: ?Sized> {
+    key: (u64, u64),
+    next_id: u64,
+    refs: HashMap<u64, Ref<T>>,
+    /// Every id issued for references to each owner, which may point to different places.
+    ids: HashMap<RefId, Vec<u64>>,
+    purge_len: PurgeLen,
+}
+
+impl<T: ?Sized> TokenTable<T> {
+    /// Creates an empty table with a fresh secret.
+    ///
+    /// # Panics
+    /// If the OS random number generator is unavailable.
+    pub fn new() -> Self {
+        let secret = || getrandom::u64().expect("failed to generate a token secret");
+        TokenTable {
+            key: (secret(), secret()),
+            next_id: 0,
+            refs: HashMap::new(),
+            ids: HashMap::new(),
+            purge_len: PurgeLen::new(),
+        }
+    }
+
+    /// The number of tokens issued, including any with dead references that have not been purged.
+    pub fn len(&self) -> usize {
+        self.refs.len()
+    }
+
+    /// Returns true if there are no tokens, even ones with dead references.
+    pub fn is_empty(&self) -> bool {
+        self.refs.is_empty()
+    }
+
+    /// Provides a token for the reference. Issuing the same reference again
+    /// gives the same token, until it is revoked. References to the same
+    /// owner which point to different places (after [Ref::map]) get
+    /// different tokens.
+    ///
+    /// Tokens with dead references are purged whenever the table has doubled
+    /// in size since the last purge, so the cost is amortized across issues.
+    pub fn issue(&mut self, weak: Ref<T>) -> u128 {
+        let existing = self.ids.get(&weak.id()).and_then(|ids| {
+            ids.iter()
+                .copied()
+                .find(|id| self.refs[id].pointer == weak.pointer)
+        });
+        if let Some(id) = existing {
+            return self.token(id);
+        }
+        if self.purge_len.is_due(self.refs.len()) {
+            self.purge();
+            self.purge_len.purged(self.refs.len());
+        }
+        let id = self.next_id;
+        // Ids are never reused, so a revoked token can not come back to life.
+        self.next_id += 1;
+        self.refs.insert(id, weak);
+        self.ids.entry(weak.id()).or_default().push(id);
+        self.token(id)
+    }
+
+    /// Gets the reference for a token issued by this table, if the token is
+    /// authentic and the reference is still alive.
+    pub fn resolve(&self, token: u128) -> Option<Ref<T>> {
+        let id = self.verify(token)?;
+        let weak = *self.refs.get(&id)?;
+        weak.is_owner_alive().then_some(weak)
+    }
+
+    /// Forgets a token, so it no longer resolves. Returns false if the token
+    /// was not authentic or already revoked.
+    pub fn revoke(&mut self, token: u128) -> bool {
+        let Some(id) = self.verify(token) else {
+            return false;
+        };
+        let Some(weak) = self.refs.remove(&id) else {
+            return false;
+        };
+        if let Some(ids) = self.ids.get_mut(&weak.id()) {
+            ids.retain(|&other| other != id);
+            if ids.is_empty() {
+                self.ids.remove(&weak.id());
+            }
+        }
+        true
+    }
+
+    /// Removes every token whose reference has died.
+    pub fn purge(&mut self) {
+        self.refs.retain(|_, weak| weak.is_owner_alive());
+        self.ids.retain(|_, ids| {
+            ids.retain(|id| self.refs.contains_key(id));
+            !ids.is_empty()
+        });
+    }
+
+    fn mac(&self, id: u64) -> u64 {
+        let mut hasher = SipHasher24::new_with_keys(self.key.0, self.key.1);
+        hasher.write_u64(id);
+        hasher.finish()
+    }
+
+    fn token(&self, id: u64) -> u128 {
+        (id as u128) << 64 | self.mac(id) as u128
+    }
+
+    fn verify(&self, token: u128) -> Option<u64> {
+        let id = (token >> 64) as u64;
+        (self.mac(id) == token as u64).then_some(id)
+    }
+}
+
+impl<T: ?Sized> Default for TokenTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,5 +1,13 @@
-use crate::{Own, pin};
+use crate::{
+    Arena, Domain, LocalOwn, Own, RcuOwn, Region, SharedOwn, WeakKeyMap, WeakVec, local_pin, pin,
+    refer,
+};
+use std::collections::HashSet;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
 
 #[test]
 fn live_ref_get_some() {
@@ -229,3 +237,1077 @@ fn replace_weak_with_null() {
     dbg!(&*o);
 }
 */
+
+#[test]
+fn arena_insert_get() {
+    let mut arena = Arena::new();
+    let a = arena.insert(1);
+    let b = arena.insert(2);
+    assert_eq!(arena.len(), 2);
+
+    let g = pin();
+    assert_eq!(a.get(&g), Some(&1));
+    assert_eq!(b.get(&g), Some(&2));
+}
+
+#[test]
+fn arena_remove_kills_ref() {
+    let mut arena = Arena::new();
+    let a = arena.insert(String::from("a"));
+    let b = arena.insert(String::from("b"));
+    let a_str = a.map(|s| s.as_str());
+
+    assert!(arena.remove(a_str));
+    assert!(!arena.remove(a));
+    assert_eq!(arena.len(), 1);
+
+    let g = pin();
+    assert_eq!(a.get(&g), None);
+    assert_eq!(a_str.get(&g), None);
+    assert_eq!(b.get(&g).map(String::as_str), Some("b"));
+}
+
+#[test]
+fn arena_reuse_does_not_resurrect() {
+    let mut arena = Arena::new();
+    let old = arena.insert(1);
+    arena.remove(old);
+    let new: Vec<_> = (0..200).map(|i| arena.insert(i)).collect();
+
+    let g = pin();
+    assert_eq!(old.get(&g), None);
+    for (i, r) in new.iter().enumerate() {
+        assert_eq!(r.get(&g), Some(&i));
+    }
+}
+
+#[test]
+fn arena_drop_kills_all() {
+    let mut arena = Arena::new();
+    let refs: Vec<_> = (0..100).map(|i| arena.insert(Box::new(i))).collect();
+    drop(arena);
+
+    let g = pin();
+    assert!(refs.iter().all(|r| r.get(&g).is_none()));
+}
+
+#[test]
+fn arena_rejects_foreign_refs() {
+    let mut arena = Arena::<i32>::new();
+    let mut other = Arena::new();
+    let a = other.insert(1);
+    let o = Own::new_box(2);
+
+    assert!(!arena.remove(a));
+    assert!(!arena.remove(o.refer()));
+    assert_eq!(a.get(&pin()), Some(&1));
+}
+
+#[test]
+fn region_alloc_get() {
+    let mut region = Region::new();
+    let a = region.alloc(42);
+    let b = region.alloc(String::from("hello"));
+    assert_eq!(region.len(), 2);
+
+    let g = pin();
+    assert_eq!(a.get(&g), Some(&42));
+    assert_eq!(b.get(&g).map(String::as_str), Some("hello"));
+}
+
+#[test]
+fn region_drop_kills_all() {
+    let mut region = Region::new();
+    let refs: Vec<_> = (0..100).map(|i| region.alloc(vec![i])).collect();
+    let unit = region.alloc(());
+    drop(region);
+
+    let g = pin();
+    assert!(refs.iter().all(|r| r.get(&g).is_none()));
+    assert_eq!(unit.get(&g), None);
+}
+
+#[test]
+fn region_counter_is_reused() {
+    let region = Region::new();
+    let mut inner = Region::new();
+    let r = inner.alloc(1);
+    drop(inner);
+    let o = Own::new_box(2);
+
+    let g = pin();
+    assert_eq!(r.get(&g), None);
+    assert_eq!(o.refer().get(&g), Some(&2));
+    drop(region);
+}
+
+#[test]
+fn ref_id_identifies_owner() {
+    let o = Own::new_box(vec![1, 2, 3]);
+    let r = o.refer();
+    let elem = r.map(|v| &v[0]);
+    assert_eq!(r.id(), o.id());
+    assert_eq!(elem.id(), o.id());
+    assert!(elem.same_owner(r));
+
+    let other = Own::new_box(vec![1, 2, 3]);
+    assert_ne!(other.id(), o.id());
+    assert!(!other.refer().same_owner(r));
+}
+
+#[test]
+fn ref_id_not_reused_after_new_from() {
+    let o = Own::new_box(1);
+    let old = o.id();
+    let o = Own::new_from(Box::new(2), o);
+    assert_ne!(o.id(), old);
+
+    let mut ids = HashSet::new();
+    assert!(ids.insert(old));
+    assert!(ids.insert(o.id()));
+    assert!(!ids.insert(o.refer().id()));
+}
+
+#[test]
+fn weak_key_map_skips_dead_keys() {
+    let a = Own::new_box(1);
+    let b = Own::new_box(2);
+    let mut map = WeakKeyMap::new();
+    map.insert(a.refer(), "a");
+    map.insert(b.refer(), "b");
+
+    let ar = a.refer();
+    drop(a);
+    assert_eq!(map.get(ar), None);
+    assert_eq!(map.get(b.refer()), Some(&"b"));
+    assert_eq!(map.iter().map(|(_, v)| *v).collect::<Vec<_>>(), ["b"]);
+
+    assert_eq!(map.len(), 2);
+    map.purge();
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn weak_key_map_mapped_keys_share_entry() {
+    let o = Own::new_box(vec![1, 2, 3]);
+    let mut map = WeakKeyMap::new();
+    map.insert(o.refer().map(|v| &v[..]), 1);
+    assert_eq!(map.insert(o.refer().map(|v| &v[1..]), 2), Some(1));
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn weak_key_map_no_resurrect_after_reuse() {
+    let o = Own::new_box(1);
+    let mut map = WeakKeyMap::new();
+    map.insert(o.refer(), "old");
+
+    let o = Own::new_from(Box::new(2), o);
+    assert_eq!(map.get(o.refer()), None);
+    assert!(map.iter().next().is_none());
+
+    map.insert(o.refer(), "new");
+    assert_eq!(map.get(o.refer()), Some(&"new"));
+}
+
+#[test]
+fn weak_key_map_purges_on_insert() {
+    let mut map = WeakKeyMap::new();
+    for i in 0..100 {
+        let o = Own::new_box(i);
+        map.insert(o.refer(), i);
+    }
+    assert!(map.len() < 100);
+    assert_eq!(map.iter().count(), 0);
+}
+
+#[test]
+fn weak_vec_iter_compacts() {
+    let owners: Vec<_> = (0..10).map(Own::new_box).collect();
+    let mut list: WeakVec<i32> = owners.iter().map(|o| o.refer()).collect();
+    let mut owners: Vec<_> = owners.into_iter().map(Some).collect();
+    for o in owners.iter_mut().step_by(2) {
+        *o = None;
+    }
+
+    assert_eq!(list.len(), 10);
+    assert_eq!(list.len_alive(), 5);
+    let g = pin();
+    assert_eq!(list.iter(&g).copied().collect::<Vec<_>>(), [1, 3, 5, 7, 9]);
+    assert_eq!(list.len(), 5);
+}
+
+#[test]
+fn weak_vec_early_drop_keeps_rest() {
+    let a = Own::new_box(1);
+    let b = Own::new_box(2);
+    let c = Own::new_box(3);
+    let mut list = WeakVec::new();
+    list.extend([a.refer(), b.refer(), c.refer()]);
+    drop(a);
+    drop(c);
+
+    let g = pin();
+    assert_eq!(list.iter(&g).next(), Some(&2));
+    assert_eq!(list.len(), 2);
+    assert_eq!(list.iter(&g).collect::<Vec<_>>(), [&2]);
+    assert_eq!(list.len(), 1);
+}
+
+#[test]
+fn weak_vec_push_during_iter() {
+    let a = Own::new_box(1);
+    let b = Own::new_box(2);
+    let mut list = WeakVec::new();
+    list.push(a.refer());
+
+    let g = pin();
+    let mut iter = list.iter(&g);
+    assert_eq!(iter.next(), Some(&1));
+    iter.push(b.refer());
+    assert_eq!(iter.next(), Some(&2));
+    assert_eq!(iter.next(), None);
+    drop(iter);
+    assert_eq!(list.len(), 2);
+}
+
+/// Waker which counts how many times it was woken.
+struct CountWaker(AtomicUsize);
+
+impl Wake for CountWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Minimal executor, which parks the thread until woken.
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct Unpark(std::thread::Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+#[test]
+fn until_dead_woken_by_drop() {
+    let o = Own::new_box(42);
+    let r = o.refer();
+
+    let count = Arc::new(CountWaker(AtomicUsize::new(0)));
+    let waker = Waker::from(count.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut future = r.until_dead();
+    assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+    assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+    assert_eq!(count.0.load(Ordering::SeqCst), 0);
+
+    drop(o);
+    assert_eq!(count.0.load(Ordering::SeqCst), 1);
+    assert!(Pin::new(&mut future).poll(&mut cx).is_ready());
+}
+
+#[test]
+fn until_dead_ready_when_dead() {
+    let o = Own::new_box(42);
+    let r = o.refer();
+    drop(o);
+    block_on(r.until_dead());
+}
+
+#[test]
+fn until_dead_across_threads() {
+    let o = Own::new_box(42);
+    let r = o.refer();
+    let handle = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        drop(o);
+    });
+    block_on(r.until_dead());
+    assert!(!r.is_alive());
+    handle.join().unwrap();
+}
+
+#[test]
+fn until_dead_dropped_future_unregisters() {
+    let o = Own::new_box(42);
+    let count = Arc::new(CountWaker(AtomicUsize::new(0)));
+    let waker = Waker::from(count.clone());
+    let mut future = o.refer().until_dead();
+    assert!(
+        Pin::new(&mut future)
+            .poll(&mut Context::from_waker(&waker))
+            .is_pending()
+    );
+    drop(future);
+    drop(o);
+    assert_eq!(count.0.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn wait_dead_times_out() {
+    let o = Own::new_box(42);
+    let r = o.refer();
+    assert!(!r.wait_dead(Some(Duration::from_millis(5))));
+    assert!(!r.wait_dead(Some(Duration::ZERO)));
+    drop(o);
+    assert!(r.wait_dead(Some(Duration::ZERO)));
+}
+
+#[test]
+fn wait_dead_woken_by_drop() {
+    let o = Own::new_box(42);
+    let r = o.refer();
+    let handle = std::thread::spawn(move || r.wait_dead(None));
+    std::thread::sleep(Duration::from_millis(10));
+    drop(o);
+    assert!(handle.join().unwrap());
+}
+
+#[test]
+fn wait_dead_woken_by_reuse() {
+    let o = Own::new_box(42);
+    let r = o.refer();
+    let handle = std::thread::spawn(move || r.wait_dead(Some(Duration::from_secs(60))));
+    std::thread::sleep(Duration::from_millis(10));
+    let o = Own::new_from(Box::new(43), o);
+    assert!(handle.join().unwrap());
+    assert!(!o.refer().wait_dead(Some(Duration::ZERO)));
+}
+
+#[test]
+fn on_kill_runs_after_invalidation() {
+    let o = Own::new_box(42);
+    let r = o.refer();
+    let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+    for i in 0..3 {
+        let log = log.clone();
+        o.on_kill(move || log.lock().unwrap().push((i, r.get(&pin()).is_some())));
+    }
+
+    let other = Own::new_box(43);
+    drop(other);
+    assert!(log.lock().unwrap().is_empty());
+
+    drop(o);
+    assert_eq!(*log.lock().unwrap(), [(0, false), (1, false), (2, false)]);
+}
+
+#[test]
+fn on_kill_runs_on_new_from() {
+    let o = Own::new_box(1);
+    let count = Arc::new(AtomicUsize::new(0));
+    let c = count.clone();
+    o.on_kill(move || {
+        c.fetch_add(1, Ordering::SeqCst);
+    });
+
+    let o = Own::new_from(Box::new(2), o);
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+    drop(o);
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn into_inner_returns_pointer() {
+    let o = Own::new_box(String::from("hello"));
+    let r = o.refer();
+    let inner = o.into_inner();
+    assert_eq!(*inner, "hello");
+    assert_eq!(r.get(&pin()), None);
+}
+
+#[test]
+fn into_inner_waits_for_readers() {
+    let o = Own::new_box(42);
+    let r = o.refer();
+    let (started, start) = std::sync::mpsc::channel();
+    let (finish, finished) = std::sync::mpsc::channel::<()>();
+    let reader = std::thread::spawn(move || {
+        let g = pin();
+        let value = r.get(&g).unwrap();
+        started.send(()).unwrap();
+        finished.recv().unwrap();
+        *value
+    });
+    start.recv().unwrap();
+    let retired = o.retire();
+    // The reader is still pinned, so the value cannot have been reclaimed.
+    assert!(!retired.is_ready());
+    let retired = retired.try_take().unwrap_err();
+    finish.send(()).unwrap();
+    assert_eq!(reader.join().unwrap(), 42);
+    assert_eq!(*retired.wait(), 42);
+}
+
+#[test]
+fn retired_waited_on_another_thread() {
+    let o = Own::new_box(42);
+    let (send, retired) = std::sync::mpsc::channel();
+    let (finish, finished) = std::sync::mpsc::channel::<()>();
+    // The retiring thread stays alive but idle while the value is awaited.
+    let retirer = std::thread::spawn(move || {
+        send.send(o.retire()).unwrap();
+        finished.recv().unwrap();
+    });
+    let retired = retired.recv().unwrap();
+    assert_eq!(*retired.wait(), 42);
+    finish.send(()).unwrap();
+    retirer.join().unwrap();
+}
+
+#[test]
+fn retired_can_be_awaited() {
+    let o = Own::new(vec![1, 2, 3]);
+    let r = o.refer();
+    let inner = block_on(o.retire());
+    assert_eq!(inner, [1, 2, 3]);
+    assert!(!r.is_alive());
+}
+
+#[test]
+fn retired_woken_once_reclaimed() {
+    let o = Own::new_box(42);
+    let r = o.refer();
+    let (started, start) = std::sync::mpsc::channel();
+    let (finish, finished) = std::sync::mpsc::channel::<()>();
+    let reader = std::thread::spawn(move || {
+        let g = pin();
+        let value = *r.get(&g).unwrap();
+        started.send(()).unwrap();
+        finished.recv().unwrap();
+        value
+    });
+    start.recv().unwrap();
+
+    let count = Arc::new(CountWaker(AtomicUsize::new(0)));
+    let waker = Waker::from(count.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut retired = o.retire();
+    assert!(Pin::new(&mut retired).poll(&mut cx).is_pending());
+    assert!(Pin::new(&mut retired).poll(&mut cx).is_pending());
+    assert_eq!(count.0.load(Ordering::SeqCst), 0);
+
+    finish.send(()).unwrap();
+    assert_eq!(reader.join().unwrap(), 42);
+    while count.0.load(Ordering::SeqCst) == 0 {
+        pin().flush();
+        std::thread::yield_now();
+    }
+    assert_eq!(count.0.load(Ordering::SeqCst), 1);
+    assert!(matches!(Pin::new(&mut retired).poll(&mut cx), Poll::Ready(b) if *b == 42));
+}
+
+#[test]
+#[should_panic]
+fn into_inner_panics_when_pinned() {
+    let o = Own::new_box(42);
+    let _g = pin();
+    o.drop_now();
+}
+
+#[test]
+fn rcu_refs_follow_publish() {
+    let mut o = RcuOwn::new(Box::new(vec![1]));
+    let r = o.refer();
+
+    let g = pin();
+    let old = r.get(&g).unwrap();
+    o.publish(Box::new(vec![2, 3]));
+    assert_eq!(old, &[1]);
+    assert_eq!(r.get(&g), Some(&vec![2, 3]));
+    assert_eq!(*o, [2, 3]);
+    drop(g);
+
+    assert_eq!(r.inspect(|v| v.len()), Some(2));
+    drop(o);
+    assert_eq!(r.get(&pin()), None);
+    assert!(!r.is_alive());
+}
+
+#[test]
+fn rcu_drops_every_value() {
+    let counter = Arc::new(());
+    let mut o = RcuOwn::new(Box::new(counter.clone()));
+    for _ in 0..10 {
+        o.publish(Box::new(counter.clone()));
+    }
+    drop(o);
+    while Arc::strong_count(&counter) > 1 {
+        pin().flush();
+    }
+}
+
+#[test]
+fn revoke_keeps_value() {
+    let mut o = Own::new_box(42);
+    let old = o.refer();
+    let mapped = old.map(|x| x);
+    let new = o.revoke();
+
+    let g = pin();
+    assert_eq!(old.get(&g), None);
+    assert_eq!(mapped.get(&g), None);
+    assert_eq!(new.get(&g), Some(&42));
+    assert_eq!(o.refer().get(&g), Some(&42));
+    assert_eq!(*o, 42);
+    assert_ne!(old.id(), new.id());
+
+    drop(o);
+    assert_eq!(new.get(&g), None);
+}
+
+#[test]
+fn revoke_wakes_waiters_and_keeps_callbacks() {
+    let mut o = Own::new_box(42);
+    let old = o.refer();
+    let count = Arc::new(AtomicUsize::new(0));
+    let c = count.clone();
+    o.on_kill(move || {
+        c.fetch_add(1, Ordering::SeqCst);
+    });
+
+    let waiter = std::thread::spawn(move || old.wait_dead(None));
+    std::thread::sleep(Duration::from_millis(10));
+    let new = o.revoke();
+    assert!(waiter.join().unwrap());
+    assert!(new.is_alive());
+    assert_eq!(count.load(Ordering::SeqCst), 0);
+
+    drop(o);
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn make_mut_revokes_refs() {
+    let mut o = Own::new(String::from("hello"));
+    let old = o.refer();
+    o.make_mut().make_ascii_uppercase();
+
+    let g = pin();
+    assert_eq!(old.get(&g), None);
+    assert_eq!(o.refer().get(&g), Some("HELLO"));
+    assert_eq!(&*o, "HELLO");
+}
+
+#[test]
+fn make_mut_waits_for_readers() {
+    let mut o = Own::new_box(vec![1, 2, 3]);
+    let r = o.refer();
+    let (started, start) = std::sync::mpsc::channel();
+    let reader = std::thread::spawn(move || {
+        let g = pin();
+        let value = r.get(&g).unwrap();
+        started.send(()).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        value.clone()
+    });
+    start.recv().unwrap();
+    o.make_mut().clear();
+    assert_eq!(reader.join().unwrap(), [1, 2, 3]);
+    assert!(o.is_empty());
+}
+
+#[test]
+fn make_mut_panics_when_pinned_without_revoking() {
+    let mut o = Own::new_box(42);
+    let r = o.refer();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _g = pin();
+        *o.make_mut() += 1;
+    }));
+    assert!(result.is_err());
+    assert_eq!(r.get(&pin()), Some(&42));
+}
+
+#[test]
+fn shared_own_dies_with_last_owner() {
+    let a = SharedOwn::new(Box::new(String::from("shared")));
+    let b = a.clone();
+    let r = refer!(b);
+    assert_eq!(a.owner_count(), 2);
+    assert!(r.same_owner(a.refer()));
+
+    drop(a);
+    assert_eq!(b.owner_count(), 1);
+    assert_eq!(r.get(&pin()).map(String::as_str), Some("shared"));
+    drop(b);
+    assert_eq!(r.get(&pin()), None);
+}
+
+#[test]
+fn shared_own_try_unwrap() {
+    let a = SharedOwn::from(Own::new_box(1));
+    let b = a.clone();
+    let r = a.refer();
+    let a = a.try_unwrap().unwrap_err();
+    drop(b);
+
+    let own = a.try_unwrap().unwrap();
+    assert_eq!(r.get(&pin()), Some(&1));
+    drop(own);
+    assert_eq!(r.get(&pin()), None);
+}
+
+#[test]
+fn new_cyclic_ref_becomes_alive() {
+    struct Parent {
+        children: Vec<Own<Box<Child>>>,
+    }
+    struct Child {
+        parent: crate::Ref<Parent>,
+    }
+
+    let parent = Own::new_cyclic(|me| {
+        assert_eq!(me.get(&pin()).map(|_| ()), None);
+        Parent {
+            children: vec![Own::new_box(Child { parent: me })],
+        }
+    });
+    let g = pin();
+    let child = &parent.children[0];
+    let back = child.parent.get(&g).unwrap();
+    assert!(std::ptr::eq(back, &*parent));
+    drop(g);
+
+    let weak = child.parent;
+    drop(parent);
+    assert!(!weak.is_alive());
+}
+
+#[test]
+fn new_cyclic_ref_is_not_dead_while_building() {
+    let counter = Arc::new(CountWaker(AtomicUsize::new(0)));
+    let waker = Waker::from(counter.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut waiter = None;
+    let mut until_dead = None;
+    let owner = Own::new_cyclic(|me: crate::Ref<i32>| {
+        assert!(!me.is_alive());
+        assert!(!me.wait_dead(Some(Duration::from_millis(1))));
+        let mut future = Box::pin(me.until_dead());
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        until_dead = Some(future);
+        waiter = Some(std::thread::spawn(move || me.wait_dead(None)));
+        7
+    });
+    let mut until_dead = until_dead.unwrap();
+    assert!(until_dead.as_mut().poll(&mut cx).is_pending());
+    assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+
+    drop(owner);
+    assert!(waiter.unwrap().join().unwrap());
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    assert!(until_dead.as_mut().poll(&mut cx).is_ready());
+}
+
+#[test]
+fn new_cyclic_panic_wakes_waiters() {
+    let (started, wait_started) = std::sync::mpsc::channel();
+    let mut waiter = None;
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        Own::<Box<i32>>::new_cyclic(|me| {
+            waiter = Some(std::thread::spawn(move || {
+                started.send(()).unwrap();
+                me.wait_dead(None)
+            }));
+            wait_started.recv().unwrap();
+            panic!("construction failed")
+        })
+    }));
+    assert!(result.is_err());
+    assert!(waiter.unwrap().join().unwrap());
+}
+
+#[test]
+fn new_cyclic_panic_keeps_ref_dead() {
+    let mut leaked = None;
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        Own::<Box<i32>>::new_cyclic(|me| {
+            leaked = Some(me);
+            panic!("construction failed")
+        })
+    }));
+    assert!(result.is_err());
+    let leaked = leaked.unwrap();
+    assert_eq!(leaked.get(&pin()), None);
+    assert!(leaked.wait_dead(Some(Duration::ZERO)));
+
+    // Recycled counters never bring the reference back to life.
+    let owners: Vec<_> = (0..8).map(Own::new_box).collect();
+    drop(owners);
+    assert_eq!(leaked.get(&pin()), None);
+}
+
+#[test]
+fn local_own_holds_rc() {
+    let shared = std::rc::Rc::new(std::cell::RefCell::new(0));
+    let o = LocalOwn::new_box(shared.clone());
+    let r = o.refer();
+    *r.get(&local_pin()).unwrap().borrow_mut() += 1;
+    assert_eq!(r.inspect(|rc| *rc.borrow()), Some(1));
+    assert_eq!(std::rc::Rc::strong_count(&shared), 2);
+
+    drop(o);
+    assert!(!r.is_alive());
+    assert_eq!(std::rc::Rc::strong_count(&shared), 1);
+}
+
+#[test]
+fn local_own_defers_drop_while_guarded() {
+    let shared = std::rc::Rc::new(());
+    let o = LocalOwn::new_box((shared.clone(), String::from("local")));
+    let r = o.refer().map(|(_, name)| name.as_str());
+
+    let outer = local_pin();
+    let inner = local_pin();
+    let value = r.get(&outer).unwrap();
+    drop(o);
+    assert!(r.get(&inner).is_none());
+    assert_eq!(value, "local");
+    drop(inner);
+    assert_eq!(std::rc::Rc::strong_count(&shared), 2);
+    drop(outer);
+    assert_eq!(std::rc::Rc::strong_count(&shared), 1);
+}
+
+/// Value which counts how many times it was dropped.
+struct Counted(Arc<AtomicUsize>);
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn hazard_guard_delays_only_protected_values() {
+    use crate::reclaim::{Hazard, HazardGuard, IsGuard, Reclaim};
+
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let a = Own::new_in(Box::new(Counted(dropped.clone())), Hazard);
+    let b = Own::new_in(Box::new(Counted(dropped.clone())), Hazard);
+    let a_ref = refer!(a);
+    let guard = HazardGuard::pin();
+    assert!(a_ref.get(&guard).is_some());
+
+    drop(a);
+    drop(b);
+    Hazard.flush();
+    assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    assert!(a_ref.get(&guard).is_none());
+
+    drop(guard);
+    Hazard.flush();
+    assert_eq!(dropped.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn hazard_refs_across_threads() {
+    use crate::reclaim::{Hazard, HazardGuard, IsGuard};
+
+    let owners: Vec<_> = (0..16).map(|i| Own::new_in(Box::new(i), Hazard)).collect();
+    let refs: Vec<_> = owners.iter().map(Own::refer).collect();
+    let reader = std::thread::spawn(move || {
+        let mut seen = 0;
+        for _ in 0..100 {
+            let guard = HazardGuard::pin();
+            for (i, r) in refs.iter().enumerate() {
+                if let Some(&value) = r.get(&guard) {
+                    assert_eq!(value, i);
+                    seen += 1;
+                }
+            }
+        }
+        seen
+    });
+    drop(owners);
+    assert!(reader.join().unwrap() <= 1600);
+}
+
+#[test]
+fn hazard_guard_protects_each_owner_once() {
+    use crate::reclaim::{Hazard, HazardGuard, IsGuard};
+
+    let owners: Vec<_> = (0..16).map(|i| Own::new_in(Box::new(i), Hazard)).collect();
+    let guard = HazardGuard::pin();
+    for _ in 0..100 {
+        for owner in &owners {
+            assert!(owner.refer().get(&guard).is_some());
+        }
+    }
+    assert_eq!(guard.protected(), 16);
+}
+
+#[test]
+fn own_has_ref_layout() {
+    use crate::{Ref, reclaim::Hazard};
+
+    assert_eq!(size_of::<Own<Box<u8>>>(), size_of::<Ref<u8>>());
+    assert_eq!(align_of::<Own<Box<u8>>>(), align_of::<Ref<u8>>());
+    assert_eq!(size_of::<Own<Box<u8>, Hazard>>(), size_of::<Ref<u8>>());
+}
+
+#[test]
+fn domain_refs_are_plain_refs() {
+    let domain = Domain::new();
+    let a = domain.own(Box::new(String::from("domain")));
+    let b = Own::new_box(String::from("global"));
+    let refs: Vec<crate::Ref<String>> = vec![refer!(a), refer!(b)];
+
+    let g = domain.pin();
+    assert_eq!(refs[0].get(&g).map(String::as_str), Some("domain"));
+    assert_eq!(refs[1].get(&g).map(String::as_str), Some("global"));
+    drop(g);
+
+    drop(a);
+    assert_eq!(domain.pooled_counters(), 1);
+    assert_eq!(refs[0].get(&pin()), None);
+}
+
+#[test]
+fn domain_owners_wait_for_global_guard() {
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let domain = Domain::new();
+    let d = domain.own(Box::new(Counted(dropped.clone())));
+    let r = d.refer();
+    let guard = pin();
+    let value = r.get(&guard).unwrap();
+    std::thread::spawn(move || {
+        drop(d);
+        drop(domain);
+        for _ in 0..8 {
+            pin().flush();
+        }
+    })
+    .join()
+    .unwrap();
+    assert_eq!(dropped.load(Ordering::SeqCst), 0);
+    assert_eq!(Arc::strong_count(&value.0), 2);
+    drop(guard);
+}
+
+#[test]
+fn padded_domain_separates_counters() {
+    let domain = Domain::padded();
+    let owners: Vec<_> = (0..4).map(|i| domain.own(Box::new(i))).collect();
+    let mut addresses: Vec<usize> = owners
+        .iter()
+        .map(|o| o._weak.current_gen as *const _ as usize)
+        .collect();
+    addresses.sort();
+    for pair in addresses.windows(2) {
+        assert!(pair[1] - pair[0] >= 128);
+    }
+    assert!(
+        owners
+            .iter()
+            .all(|o| (o._weak.current_gen as *const _ as usize).is_multiple_of(128))
+    );
+
+    let r = owners[2].refer();
+    assert_eq!(r.get(&domain.pin()), Some(&2));
+    drop(owners);
+    assert_eq!(r.get(&pin()), None);
+}
+
+#[test]
+#[cfg(not(loom))]
+fn compact_ref_round_trip() {
+    use crate::{CompactRef, Ref};
+
+    assert_eq!(size_of::<CompactRef<i32>>(), 16);
+    let domain = Domain::padded();
+    let a = Own::new_box(1);
+    let b = domain.own(Box::new(2));
+    for (weak, value) in [(a.refer(), &*a), (b.refer(), &*b)] {
+        let compact = CompactRef::from(weak);
+        assert_eq!(compact.get(&pin()), Some(value));
+        let back = Ref::from(compact);
+        assert_eq!(back.id(), weak.id());
+        assert!(std::ptr::eq(back.get(&pin()).unwrap(), value));
+    }
+
+    let compact = CompactRef::from(a.refer());
+    drop(a);
+    assert!(!compact.is_alive());
+    assert_eq!(compact.get(&pin()), None);
+    assert_eq!(CompactRef::<i32>::null().get(&pin()), None);
+    assert!(CompactRef::from(Ref::<i32>::null()).get(&pin()).is_none());
+}
+
+/// Held by tests which retire counters, so that they can count them exactly.
+static RETIRING: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[test]
+#[cfg(not(loom))]
+fn counters_retire_before_u32_overflow() {
+    use crate::CompactRef;
+
+    let _retiring = RETIRING.lock().unwrap();
+    let o = Own::new_box(0);
+    let counter = o._weak.current_gen;
+    drop(o);
+    // The counter is idle in the local pool, so skip ahead to the last usable generation.
+    counter.store(u32::MAX as usize - 1, Ordering::SeqCst);
+
+    let o = Own::new_box(1);
+    assert!(std::ptr::eq(o._weak.current_gen, counter));
+    let compact = CompactRef::from(o.refer());
+    assert_eq!(compact.get(&pin()), Some(&1));
+    let retired = crate::stats().counters_retired;
+    drop(o);
+    assert_eq!(crate::stats().counters_retired, retired + 1);
+    assert_eq!(compact.get(&pin()), None);
+
+    let next = Own::new_box(2);
+    assert!(!std::ptr::eq(next._weak.current_gen, counter));
+}
+
+#[test]
+fn arena_counts_retired_slot_once() {
+    let _retiring = RETIRING.lock().unwrap();
+    let o = Own::new_box(0);
+    let counter = o._weak.current_gen;
+    drop(o);
+    // The arena takes the idle counter for its first slot.
+    counter.store(u32::MAX as usize - 1, Ordering::SeqCst);
+
+    let mut arena = Arena::new();
+    let r = arena.insert(1);
+    assert!(std::ptr::eq(r.current_gen, counter));
+    let retired = crate::stats().counters_retired;
+    assert!(arena.remove(r));
+    drop(arena);
+    assert_eq!(crate::stats().counters_retired, retired + 1);
+}
+
+#[test]
+fn handle_table_survives_slot_reuse() {
+    use crate::{Handle, HandleTable};
+
+    let mut table = HandleTable::new();
+    let old = table.insert(String::from("old"));
+    assert!(table.remove(old));
+    assert!(!table.remove(old));
+
+    // Wait for the slot to be vacated, then fill the table until it is reused.
+    let g = pin();
+    g.flush();
+    drop(g);
+    pin().flush();
+    let new: Vec<_> = (0..64).map(|i| table.insert(i.to_string())).collect();
+    assert_eq!(table.len(), 64);
+    assert!(!table.contains(old));
+    assert_eq!(table.get(old, &pin()), None);
+    assert!(!new.contains(&old));
+
+    let handle = new[10];
+    let bits = handle.to_bits();
+    assert_eq!(Handle::<String>::from_bits(bits), handle);
+    assert_eq!(table.get(Handle::from_bits(bits), &pin()).unwrap(), "10");
+}
+
+#[test]
+fn handle_table_refs() {
+    use crate::HandleTable;
+
+    let mut table = HandleTable::new();
+    let h = table.insert(vec![1, 2, 3]);
+    let r = table.refer(h).unwrap();
+    assert_eq!(table.handle_of(r), Some(h));
+    assert_eq!(r.get(&pin()), Some(&vec![1, 2, 3]));
+
+    table.remove(h);
+    assert_eq!(r.get(&pin()), None);
+    assert_eq!(table.handle_of(r), None);
+    assert_eq!(size_of::<crate::Handle<Vec<i32>>>(), 8);
+}
+
+#[test]
+fn token_table_rejects_bad_tokens() {
+    use crate::TokenTable;
+
+    let a = Own::new_box(1);
+    let b = Own::new_box(2);
+    let mut tokens = TokenTable::new();
+    let ta = tokens.issue(a.refer());
+    let tb = tokens.issue(b.refer());
+    assert_eq!(tokens.issue(a.refer()), ta);
+    assert_ne!(ta, tb);
+
+    // Forged: valid ids with a guessed MAC, and a valid MAC with another id
+    assert!(tokens.resolve(ta >> 64 << 64).is_none());
+    assert!(tokens.resolve(ta ^ 1 << 64).is_none());
+    assert!(
+        tokens
+            .resolve(tb & u64::MAX as u128 | ta >> 64 << 64)
+            .is_none()
+    );
+
+    // Cross-table: same id, different secret
+    let mut other = TokenTable::new();
+    assert_eq!(other.issue(a.refer()) >> 64, ta >> 64);
+    assert!(other.resolve(ta).is_none());
+    assert_eq!(tokens.resolve(ta).unwrap().get(&pin()), Some(&1));
+
+    // Stale: revoked or dead
+    assert!(tokens.revoke(ta));
+    assert!(!tokens.revoke(ta));
+    assert!(tokens.resolve(ta).is_none());
+    assert_ne!(tokens.issue(a.refer()), ta);
+    drop(b);
+    assert!(tokens.resolve(tb).is_none());
+}
+
+#[test]
+fn token_table_separates_mapped_refs() {
+    use crate::TokenTable;
+
+    let o = Own::new(String::from("hello world"));
+    let mut tokens = TokenTable::<str>::new();
+    let whole = tokens.issue(o.refer());
+    let tail = tokens.issue(o.refer().map(|s| &s[6..]));
+    let head = tokens.issue(o.refer().map(|s| &s[..5]));
+    assert_ne!(whole, tail);
+    assert_ne!(tail, head);
+    assert_eq!(tokens.issue(o.refer().map(|s| &s[6..])), tail);
+
+    let g = pin();
+    assert_eq!(tokens.resolve(whole).unwrap().get(&g), Some("hello world"));
+    assert_eq!(tokens.resolve(tail).unwrap().get(&g), Some("world"));
+    assert_eq!(tokens.resolve(head).unwrap().get(&g), Some("hello"));
+
+    assert!(tokens.revoke(tail));
+    assert_eq!(tokens.issue(o.refer()), whole);
+    assert_eq!(tokens.len(), 2);
+}
+
+#[test]
+fn token_table_purges_dead() {
+    use crate::TokenTable;
+
+    let keep = Own::new_box(0);
+    let mut tokens = TokenTable::new();
+    let token = tokens.issue(keep.refer());
+    for i in 0..100 {
+        let own = Own::new_box(i);
+        tokens.issue(own.refer());
+    }
+    assert!(tokens.len() < 50);
+    assert_eq!(tokens.resolve(token).unwrap().get(&pin()), Some(&0));
+    tokens.purge();
+    assert_eq!(tokens.len(), 1);
+}
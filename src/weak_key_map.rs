@@ -0,0 +1,153 @@
+use crate::guts::{Ref, RefId};
+use std::collections::HashMap;
+use std::collections::hash_map;
+
+/// Collections will never purge themselves when smaller than this.
+const MIN_PURGE_LEN: usize = 16;
+
+/// Decides when a collection of references should purge dead entries:
+/// whenever it has doubled in size since the last purge, so that the cost is
+/// amortized across inserts.
+pub(crate) struct PurgeLen(usize);
+
+impl PurgeLen {
+    pub(crate) fn new() -> Self {
+        PurgeLen(MIN_PURGE_LEN)
+    }
+
+    /// Returns true if a collection of `len` entries should purge before inserting.
+    pub(crate) fn is_due(&self, len: usize) -> bool {
+        len >= self.0
+    }
+
+    /// Records that the collection was purged down to `len` entries.
+    pub(crate) fn purged(&mut self, len: usize) {
+        self.0 = MIN_PURGE_LEN.max(len * 2);
+    }
+}
+
+/// A map keyed by the owner of a [Ref], which forgets entries once the owner is dropped.
+///
+/// This is useful for attaching side tables of data to objects owned
+/// elsewhere. Entries with a dead key are never returned, and are removed by
+/// [WeakKeyMap::purge] or automatically as the map grows. Because keys are
+/// compared by [RefId], an entry is never resurrected when the generation
+/// counter of a dead key gets reused.
+///
+/// ```
+///# use weakref::{Own, WeakKeyMap};
+/// let a = Own::new_box("a");
+/// let b = Own::new_box("b");
+/// let mut names = WeakKeyMap::new();
+/// names.insert(a.refer(), 1);
+/// names.insert(b.refer(), 2);
+///
+/// drop(a);
+/// assert_eq!(names.get(b.refer()), Some(&2));
+/// assert_eq!(names.iter().count(), 1);
+/// ```
+pub struct WeakKeyMap<K: ?Sized, V> {
+    entries: HashMap<RefId, (Ref<K>, V)>,
+    purge_len: PurgeLen,
+}
+
+impl<K: ?Sized, V> WeakKeyMap<K, V> {
+    /// Creates an empty map.
+    pub fn new() -> Self {
+        WeakKeyMap {
+            entries: HashMap::new(),
+            purge_len: PurgeLen::new(),
+        }
+    }
+
+    /// The number of entries, including any with dead keys that have not been purged.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if there are no entries, even ones with dead keys.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Inserts a value for the owner of `key`, returning the previous value if
+    /// the owner is still alive.
+    ///
+    /// Entries with dead keys are purged whenever the map has doubled in size
+    /// since the last purge, so the cost is amortized across inserts.
+    pub fn insert(&mut self, key: Ref<K>, value: V) -> Option<V> {
+        if self.purge_len.is_due(self.entries.len()) {
+            self.purge();
+            self.purge_len.purged(self.entries.len());
+        }
+        let (_, previous) = self.entries.insert(key.id(), (key, value))?;
+        key.is_owner_alive().then_some(previous)
+    }
+
+    /// Gets the value for the owner of `key`, if it is still alive.
+    pub fn get(&self, key: Ref<K>) -> Option<&V> {
+        let (key, value) = self.entries.get(&key.id())?;
+        key.is_owner_alive().then_some(value)
+    }
+
+    /// Gets the value for the owner of `key` mutably, if it is still alive.
+    pub fn get_mut(&mut self, key: Ref<K>) -> Option<&mut V> {
+        let (key, value) = self.entries.get_mut(&key.id())?;
+        key.is_owner_alive().then_some(value)
+    }
+
+    /// Returns true if there is a value for the owner of `key`, and it is still alive.
+    pub fn contains_key(&self, key: Ref<K>) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Removes the value for the owner of `key`, returning it if the owner is still alive.
+    pub fn remove(&mut self, key: Ref<K>) -> Option<V> {
+        let (key, value) = self.entries.remove(&key.id())?;
+        key.is_owner_alive().then_some(value)
+    }
+
+    /// Removes every entry whose key has died.
+    pub fn purge(&mut self) {
+        self.entries.retain(|_, (key, _)| key.is_owner_alive());
+    }
+
+    /// Iterates over the entries whose keys are still alive.
+    ///
+    /// Be aware there are no ordering guarentees, as with [Ref::is_alive].
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            entries: self.entries.values(),
+        }
+    }
+}
+
+impl<K: ?Sized, V> Default for WeakKeyMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, K: ?Sized, V> IntoIterator for &'a WeakKeyMap<K, V> {
+    type Item = (Ref<K>, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the live entries of a [WeakKeyMap].
+pub struct Iter<'a, K: ?Sized, V> {
+    entries: hash_map::Values<'a, RefId, (Ref<K>, V)>,
+}
+
+impl<'a, K: ?Sized, V> Iterator for Iter<'a, K, V> {
+    type Item = (Ref<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries
+            .find(|(key, _)| key.is_owner_alive())
+            .map(|(key, value)| (*key, value))
+    }
+}
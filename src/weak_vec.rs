@@ -0,0 +1,138 @@
+use crate::guts::Ref;
+use crossbeam_epoch::Guard;
+
+/// A list of references which drops dead entries as it goes.
+///
+/// This replaces the common pattern of a `Vec<Ref<T>>` plus
+/// `retain(|r| r.is_alive())`, for example in observer lists. Iterating with
+/// [WeakVec::iter] only yields live values, and compacts away any dead
+/// entries it passes over, so cleanup is amortized across iterations.
+///
+/// ```
+///# use weakref::{Own, WeakVec, pin};
+/// let a = Own::new_box(1);
+/// let b = Own::new_box(2);
+/// let mut observers = WeakVec::new();
+/// observers.push(a.refer());
+/// observers.push(b.refer());
+///
+/// drop(a);
+/// let sum: i32 = observers.iter(&pin()).sum();
+/// assert_eq!(sum, 2);
+/// assert_eq!(observers.len(), 1);
+/// ```
+pub struct WeakVec<T: ?Sized> {
+    refs: Vec<Ref<T>>,
+}
+
+impl<T: ?Sized> WeakVec<T> {
+    /// Creates an empty list.
+    pub fn new() -> Self {
+        WeakVec { refs: Vec::new() }
+    }
+
+    /// Appends a reference to the end of the list.
+    pub fn push(&mut self, weak: Ref<T>) {
+        self.refs.push(weak);
+    }
+
+    /// The number of entries, including dead ones which have not been compacted yet.
+    pub fn len(&self) -> usize {
+        self.refs.len()
+    }
+
+    /// Returns true if there are no entries, even dead ones.
+    pub fn is_empty(&self) -> bool {
+        self.refs.is_empty()
+    }
+
+    /// Counts the entries which are still alive.
+    ///
+    /// Be aware there are no ordering guarentees, as with [Ref::is_alive].
+    pub fn len_alive(&self) -> usize {
+        self.refs.iter().filter(|r| r.is_alive()).count()
+    }
+
+    /// Removes every dead entry right away.
+    pub fn compact(&mut self) {
+        self.refs.retain(|r| r.is_alive());
+    }
+
+    /// Iterates over the live values, removing dead entries along the way.
+    ///
+    /// Every value borrows from `guard`, so the whole iteration only needs to
+    /// pin once. New entries can be added with [Iter::push] while iterating,
+    /// and will be visited before the iterator finishes.
+    pub fn iter<'v, 'g>(&'v mut self, guard: &'g Guard) -> Iter<'v, 'g, T> {
+        Iter {
+            refs: &mut self.refs,
+            read: 0,
+            write: 0,
+            guard,
+        }
+    }
+}
+
+impl<T: ?Sized> Default for WeakVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized> Extend<Ref<T>> for WeakVec<T> {
+    fn extend<I: IntoIterator<Item = Ref<T>>>(&mut self, iter: I) {
+        self.refs.extend(iter);
+    }
+}
+
+impl<T: ?Sized> FromIterator<Ref<T>> for WeakVec<T> {
+    fn from_iter<I: IntoIterator<Item = Ref<T>>>(iter: I) -> Self {
+        WeakVec {
+            refs: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// Iterator over the live values of a [WeakVec], created by [WeakVec::iter].
+///
+/// Live entries are shifted down over dead ones as they are visited. If the
+/// iterator is dropped early, the rest of the list is kept as is.
+pub struct Iter<'v, 'g, T: ?Sized> {
+    refs: &'v mut Vec<Ref<T>>,
+    /// The next entry to be visited.
+    read: usize,
+    /// Where the next live entry should be moved to.
+    write: usize,
+    guard: &'g Guard,
+}
+
+impl<T: ?Sized> Iter<'_, '_, T> {
+    /// Appends a reference to the end of the list, which this iterator will visit.
+    pub fn push(&mut self, weak: Ref<T>) {
+        self.refs.push(weak);
+    }
+}
+
+impl<'g, T: ?Sized + 'g> Iterator for Iter<'_, 'g, T> {
+    type Item = &'g T;
+
+    fn next(&mut self) -> Option<&'g T> {
+        while let Some(&weak) = self.refs.get(self.read) {
+            self.read += 1;
+            if let Some(value) = weak.get(self.guard) {
+                self.refs[self.write] = weak;
+                self.write += 1;
+                return Some(value);
+            }
+        }
+        None
+    }
+}
+
+impl<T: ?Sized> Drop for Iter<'_, '_, T> {
+    fn drop(&mut self) {
+        let len = self.refs.len();
+        self.refs.copy_within(self.read..len, self.write);
+        self.refs.truncate(self.write + len - self.read);
+    }
+}
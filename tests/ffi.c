@@ -0,0 +1,48 @@
+#include "weakref.h"
+
+#include <stddef.h>
+
+_Static_assert(sizeof(weakref_ref_t) == 3 * sizeof(void *), "weakref_ref_t size");
+_Static_assert(offsetof(weakref_ref_t, expected_gen) == sizeof(void *), "expected_gen offset");
+_Static_assert(offsetof(weakref_ref_t, pointer) == 2 * sizeof(void *), "pointer offset");
+
+static int destroyed = 0;
+
+static void destroy(void *data) {
+    destroyed += *(int *)data;
+}
+
+/* Exits with the line of the first failed check, or 0 on success. */
+#define CHECK(cond) if (!(cond)) return __LINE__
+
+int main(void) {
+    static int value = 42;
+    weakref_own_t *own = weakref_own_new(&value, destroy);
+    weakref_ref_t ref = weakref_own_refer(own);
+    weakref_ref_t copy = ref;
+    CHECK(weakref_ref_is_alive(copy));
+
+    weakref_guard_t *guard = weakref_pin();
+    int *data = weakref_ref_get(copy, guard);
+    CHECK(data == &value);
+    weakref_own_drop(own);
+    CHECK(!weakref_ref_is_alive(ref));
+    CHECK(weakref_ref_get(ref, guard) == NULL);
+    /* Still pinned, so the payload can not have been destroyed */
+    CHECK(*data == 42);
+    CHECK(destroyed == 0);
+    weakref_unpin(guard);
+
+    weakref_ref_t null = weakref_ref_null();
+    guard = weakref_pin();
+    CHECK(weakref_ref_get(null, guard) == NULL);
+    weakref_unpin(guard);
+    weakref_own_drop(NULL);
+    weakref_unpin(NULL);
+
+    for (int i = 0; i < 100 && destroyed == 0; i++) {
+        weakref_flush();
+    }
+    CHECK(destroyed == 42);
+    return 0;
+}
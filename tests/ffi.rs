@@ -0,0 +1,116 @@
+//! Checks the C ABI from C, by building the library as a static library and
+//! linking the program in `ffi.c` against it, as a C application would.
+#![cfg(all(feature = "ffi", unix))]
+
+use std::path::Path;
+use std::process::Command;
+
+const MANIFEST_DIR: &str = env!("CARGO_MANIFEST_DIR");
+
+#[test]
+fn ffi_c_program() {
+    let out = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ffi");
+    let status = Command::new(env!("CARGO"))
+        .args(["rustc", "--quiet", "--lib", "--features", "ffi"])
+        .args(["--crate-type", "staticlib", "--target-dir"])
+        .arg(&out)
+        .current_dir(MANIFEST_DIR)
+        .status()
+        .unwrap();
+    assert!(status.success(), "failed to build the static library");
+
+    let program = out.join("ffi_test");
+    let status = Command::new(std::env::var("CC").unwrap_or_else(|_| "cc".into()))
+        .arg(Path::new(MANIFEST_DIR).join("tests/ffi.c"))
+        .arg("-I")
+        .arg(Path::new(MANIFEST_DIR).join("include"))
+        .args(["-std=c11", "-Wall", "-Werror", "-o"])
+        .arg(&program)
+        .arg(out.join("debug/libweakref.a"))
+        .args(["-lpthread", "-ldl", "-lm"])
+        .status()
+        .unwrap();
+    assert!(status.success(), "failed to compile ffi.c");
+
+    let status = Command::new(&program).status().unwrap();
+    assert_eq!(status.code(), Some(0), "check failed on this line of ffi.c");
+}
+
+/// Renders a Rust parameter or return type from `src/ffi.rs` as C.
+fn c_type(rust: &str, name: &str) -> String {
+    let c = match rust {
+        "" => "void",
+        "bool" => "bool",
+        "*mut c_void" => "void *",
+        "*mut WeakrefOwn" => "weakref_own_t *",
+        "*const WeakrefOwn" => "const weakref_own_t *",
+        "WeakrefRef" => "weakref_ref_t",
+        "*mut Guard" => "weakref_guard_t *",
+        "*const Guard" => "const weakref_guard_t *",
+        "WeakrefDestroy" => return format!("void (*{name})(void *data)"),
+        other => panic!("no C type for `{other}`, add it to this test and the header"),
+    };
+    if c.ends_with('*') || name.is_empty() {
+        format!("{c}{name}")
+    } else {
+        format!("{c} {name}")
+    }
+}
+
+fn squash(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The header is written by hand, so make sure it declares exactly the
+/// functions exported by `src/ffi.rs`, with the same signatures.
+#[test]
+fn ffi_header_matches_exports() {
+    let source = std::fs::read_to_string(Path::new(MANIFEST_DIR).join("src/ffi.rs")).unwrap();
+    let header =
+        std::fs::read_to_string(Path::new(MANIFEST_DIR).join("include/weakref.h")).unwrap();
+    let header = squash(&header);
+
+    let mut exported = Vec::new();
+    for item in source.split("extern \"C\" fn ").skip(1) {
+        let signature = squash(&item[..item.find('{').unwrap()]);
+        let (name, rest) = signature.split_once('(').unwrap();
+        let (params, ret) = rest.rsplit_once(')').unwrap();
+        let params: Vec<String> = params
+            .split(',')
+            .map(str::trim)
+            .filter(|param| !param.is_empty())
+            .map(|param| {
+                let (name, ty) = param.split_once(':').unwrap();
+                c_type(ty.trim(), name.trim())
+            })
+            .collect();
+        let params = if params.is_empty() {
+            "void".to_string()
+        } else {
+            params.join(", ")
+        };
+        let ret = c_type(ret.trim().trim_start_matches("->").trim(), "");
+        let separator = if ret.ends_with('*') { "" } else { " " };
+        let declaration = format!("{ret}{separator}{name}({params});");
+        assert!(
+            header.contains(&declaration),
+            "include/weakref.h is missing `{declaration}`"
+        );
+        exported.push(name.to_string());
+    }
+
+    // Every identifier followed by a parenthesis is a function.
+    let mut declared: Vec<&str> = header
+        .match_indices('(')
+        .map(|(end, _)| {
+            let start = header[..end]
+                .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .map_or(0, |i| i + 1);
+            &header[start..end]
+        })
+        .filter(|name| name.starts_with("weakref_"))
+        .collect();
+    declared.sort();
+    exported.sort();
+    assert_eq!(declared, exported);
+}